@@ -0,0 +1,162 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{anyhow, Context};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use which::which;
+
+/// GitHub rejects app JWTs that live longer than 10 minutes
+const JWT_LIFETIME: chrono::Duration = chrono::Duration::minutes(9);
+
+/// Backdate the issued-at time to tolerate clock drift between us and the GitHub host
+const JWT_CLOCK_DRIFT: chrono::Duration = chrono::Duration::seconds(60);
+
+/// Token variables for github.com and GHE.com
+const GITHUB_COM_TOKEN_VARS: &[&str] = &["GH_TOKEN", "GITHUB_TOKEN"];
+
+/// Token variables for GHES hosts
+const ENTERPRISE_TOKEN_VARS: &[&str] = &["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"];
+
+/// Credentials for authenticating as a GitHub App
+#[derive(Clone)]
+pub struct AppCredentials {
+    app_id: u64,
+    key: EncodingKey,
+}
+
+#[derive(serde::Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+impl AppCredentials {
+    pub fn new(app_id: u64, private_key_pem: &[u8]) -> anyhow::Result<Self> {
+        let key = EncodingKey::from_rsa_pem(private_key_pem)
+            .context("Private key is not a valid RSA key in PEM format")?;
+        Ok(AppCredentials { app_id, key })
+    }
+
+    pub fn from_file(app_id: u64, private_key: &Path) -> anyhow::Result<Self> {
+        let pem = std::fs::read(private_key)
+            .with_context(|| format!("Failed to read private key {}", private_key.display()))?;
+        AppCredentials::new(app_id, &pem)
+    }
+
+    /// Sign a short lived JWT identifying the app
+    pub fn jwt(&self) -> anyhow::Result<String> {
+        let now = Utc::now();
+        let claims = Claims {
+            iat: (now - JWT_CLOCK_DRIFT).timestamp(),
+            exp: (now + JWT_LIFETIME).timestamp(),
+            iss: self.app_id.to_string(),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .context("Failed to sign GitHub App JWT")
+    }
+}
+
+/// Find a token for the host, the same way `gh` would: the environment first, then whatever
+/// `gh auth login` stored for the host, and finally the token file if one was given
+pub fn resolve_token(host: &str, token_file: Option<&Path>) -> anyhow::Result<String> {
+    for var in token_env_vars(host) {
+        if let Some(token) = env_token(var) {
+            log::debug!("Using token from ${}", var);
+            return Ok(token);
+        }
+    }
+    if token_env_vars(host) == ENTERPRISE_TOKEN_VARS {
+        for var in GITHUB_COM_TOKEN_VARS.iter().filter(|var| env_token(var).is_some()) {
+            log::debug!("Not using ${} for {}, it's only for github.com and GHE.com, like with `gh`", var, host);
+        }
+    }
+
+    let gh_failure = match gh_auth_token(host) {
+        Ok(Some(token)) => {
+            log::debug!("Using token from `gh auth token -h {}`", host);
+            return Ok(token);
+        }
+        Ok(None) => format!("`gh` has no token for {}", host),
+        Err(e) => format!("{:#}", e),
+    };
+    log::debug!("Could not get a token from `gh`: {}", gh_failure);
+
+    if let Some(path) = token_file {
+        log::debug!("Using token from {}", path.display());
+        let token = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read token file {}", path.display()))?;
+        return Ok(token.trim().to_string());
+    }
+
+    Err(anyhow!(
+        "No GitHub token found for {} ({}). Run `gh auth login -h {}`, set {}, or pass --token-file",
+        host, gh_failure, host, token_env_vars(host).join(" or ")
+    ))
+}
+
+/// The token in an environment variable, if it's set to anything
+fn env_token(var: &str) -> Option<String> {
+    std::env::var(var).ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Environment variables `gh` reads tokens from. GHES hosts have their own, so a github.com token
+/// in the environment never gets sent to an enterprise host by accident.
+fn token_env_vars(host: &str) -> &'static [&'static str] {
+    let host = host.split(':').next().unwrap_or(host);
+    if host == "github.com" || host.ends_with(".ghe.com") {
+        GITHUB_COM_TOKEN_VARS
+    } else {
+        ENTERPRISE_TOKEN_VARS
+    }
+}
+
+/// Ask `gh` for the token it has stored for the host. `None` if it isn't logged in to it.
+fn gh_auth_token(host: &str) -> anyhow::Result<Option<String>> {
+    let cmd = which("gh").context("`gh` was not found on the PATH")?;
+    let output = Command::new(cmd)
+        .args(["auth", "token", "-h", host])
+        .output()
+        .context("Failed to run `gh auth token`")?;
+
+    let token = String::from_utf8(output.stdout)?.trim().to_string();
+    Ok((output.status.success() && !token.is_empty()).then_some(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    use super::{token_env_vars, AppCredentials};
+
+    const PRIVATE_KEY: &[u8] = include_bytes!("../tests/fixtures/app-private-key.pem");
+
+    #[test]
+    fn test_jwt_claims() {
+        let credentials = AppCredentials::new(12345, PRIVATE_KEY).unwrap();
+        let jwt = credentials.jwt().unwrap();
+
+        let mut validation = Validation::default();
+        validation.insecure_disable_signature_validation();
+        let claims = jsonwebtoken::decode::<serde_json::Value>(&jwt, &DecodingKey::from_secret(&[]), &validation)
+            .unwrap()
+            .claims;
+        assert_eq!(claims["iss"], "12345");
+        assert!(claims["exp"].as_i64().unwrap() - claims["iat"].as_i64().unwrap() <= 600);
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(AppCredentials::new(12345, b"not a key").is_err());
+    }
+
+    #[test]
+    fn test_token_env_vars() {
+        assert_eq!(token_env_vars("github.com"), &["GH_TOKEN", "GITHUB_TOKEN"]);
+        assert_eq!(token_env_vars("octocorp.ghe.com"), &["GH_TOKEN", "GITHUB_TOKEN"]);
+        assert_eq!(token_env_vars("localhost:3000"), &["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"]);
+        assert_eq!(token_env_vars("github.example.com"), &["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"]);
+    }
+}
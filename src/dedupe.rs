@@ -0,0 +1,186 @@
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::gh::WebhookDelivery;
+
+/// How many GUIDs to remember. Redeliveries are usually requested soon after the original, so
+/// only the most recent events matter.
+const SEEN_GUIDS_CAPACITY: usize = 1000;
+
+/// Which attempts of an event to forward, when GitHub delivered it more than once
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dedupe {
+    /// Forward every attempt, redeliveries included
+    #[default]
+    All,
+    /// Forward an event only the first time an attempt of it shows up
+    First,
+    /// Forward only the latest attempt of an event among those that show up together
+    Latest,
+}
+
+/// GUIDs of recently forwarded events, forgetting the oldest ones once full
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+pub struct SeenGuids {
+    order: VecDeque<String>,
+    guids: HashSet<String>,
+}
+
+impl SeenGuids {
+    /// Remember a GUID. `false` if it was already there.
+    pub fn insert(&mut self, guid: &str) -> bool {
+        if self.guids.contains(guid) {
+            return false;
+        }
+        if self.order.len() >= SEEN_GUIDS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.guids.remove(&oldest);
+            }
+        }
+        self.order.push_back(guid.to_string());
+        self.guids.insert(guid.to_string());
+        true
+    }
+
+    pub fn contains(&self, guid: &str) -> bool {
+        self.guids.contains(guid)
+    }
+}
+
+impl From<Vec<String>> for SeenGuids {
+    fn from(guids: Vec<String>) -> Self {
+        let mut seen = SeenGuids::default();
+        for guid in guids {
+            seen.insert(&guid);
+        }
+        seen
+    }
+}
+
+impl From<SeenGuids> for Vec<String> {
+    fn from(seen: SeenGuids) -> Self {
+        seen.order.into()
+    }
+}
+
+/// Drop the deliveries the policy says not to forward. Deliveries must be sorted oldest first.
+/// Ones without a GUID can't be matched up with other attempts and are kept. `seen` only changes
+/// once a delivery is actually forwarded, so an attempt that never made it doesn't hold back the others.
+pub fn filter(deliveries: Vec<WebhookDelivery>, policy: Dedupe, seen: &SeenGuids) -> Vec<WebhookDelivery> {
+    match policy {
+        Dedupe::All => deliveries,
+        Dedupe::First => {
+            let mut batch = HashSet::new();
+            deliveries.into_iter()
+                .filter(|delivery| match &delivery.guid {
+                    Some(_) if already_forwarded(delivery, policy, seen) => {
+                        log::debug!("Skipping delivery {}, an attempt of it was already forwarded", delivery.id);
+                        false
+                    }
+                    Some(guid) if !batch.insert(guid.clone()) => {
+                        log::debug!("Skipping delivery {}, an earlier attempt of {} is here too", delivery.id, guid);
+                        false
+                    }
+                    _ => true,
+                })
+                .collect()
+        }
+        Dedupe::Latest => {
+            let mut batch = HashSet::new();
+            let mut latest: Vec<WebhookDelivery> = deliveries.into_iter()
+                .rev()
+                .filter(|delivery| match &delivery.guid {
+                    Some(guid) if !batch.insert(guid.clone()) => {
+                        log::debug!("Skipping delivery {}, a later attempt of {} is here too", delivery.id, guid);
+                        false
+                    }
+                    _ => true,
+                })
+                .collect();
+            latest.reverse();
+            latest
+        }
+    }
+}
+
+/// Whether the policy skips this delivery because another attempt of the event was already forwarded
+pub fn already_forwarded(delivery: &WebhookDelivery, policy: Dedupe, seen: &SeenGuids) -> bool {
+    policy == Dedupe::First && delivery.guid.as_deref().is_some_and(|guid| seen.contains(guid))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::gh::WebhookDelivery;
+
+    use super::{already_forwarded, filter, Dedupe, SeenGuids, SEEN_GUIDS_CAPACITY};
+
+    fn delivery(id: u64, guid: &str) -> WebhookDelivery {
+        WebhookDelivery {
+            id,
+            guid: Some(guid.to_string()),
+            delivered_at: Utc::now(),
+            redelivery: id > 1,
+            event: "issues".to_string(),
+            action: None,
+        }
+    }
+
+    fn ids(deliveries: &[WebhookDelivery]) -> Vec<u64> {
+        deliveries.iter().map(|delivery| delivery.id).collect()
+    }
+
+    #[test]
+    fn test_filter_all() {
+        let seen = SeenGuids::default();
+        let kept = filter(vec![delivery(1, "a"), delivery(2, "a")], Dedupe::All, &seen);
+        assert_eq!(ids(&kept), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_filter_first() {
+        let mut seen = SeenGuids::default();
+        let kept = filter(vec![delivery(1, "a"), delivery(2, "b"), delivery(3, "a")], Dedupe::First, &seen);
+        assert_eq!(ids(&kept), vec![1, 2]);
+
+        seen.insert("b");
+        let kept = filter(vec![delivery(4, "b"), delivery(5, "c")], Dedupe::First, &seen);
+        assert_eq!(ids(&kept), vec![5]);
+    }
+
+    #[test]
+    fn test_filter_first_keeps_attempts_of_events_never_forwarded() {
+        let seen = SeenGuids::default();
+        let kept = filter(vec![delivery(1, "a")], Dedupe::First, &seen);
+        assert_eq!(ids(&kept), vec![1]);
+
+        // delivery 1 was never forwarded, so its redelivery still is
+        let kept = filter(vec![delivery(2, "a")], Dedupe::First, &seen);
+        assert_eq!(ids(&kept), vec![2]);
+        assert!(!already_forwarded(&delivery(1, "a"), Dedupe::First, &seen));
+    }
+
+    #[test]
+    fn test_filter_latest() {
+        let seen = SeenGuids::default();
+        let kept = filter(vec![delivery(1, "a"), delivery(2, "b"), delivery(3, "a")], Dedupe::Latest, &seen);
+        assert_eq!(ids(&kept), vec![2, 3]);
+
+        let kept = filter(vec![delivery(4, "a")], Dedupe::Latest, &seen);
+        assert_eq!(ids(&kept), vec![4]);
+    }
+
+    #[test]
+    fn test_seen_guids_are_bounded() {
+        let mut seen = SeenGuids::default();
+        for i in 0..=SEEN_GUIDS_CAPACITY {
+            seen.insert(&i.to_string());
+        }
+        assert!(!seen.contains("0"));
+        assert!(seen.contains("1"));
+        assert_eq!(Vec::from(seen).len(), SEEN_GUIDS_CAPACITY);
+    }
+}
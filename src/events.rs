@@ -0,0 +1,188 @@
+/// Events a webhook can subscribe to. GHES only knows a subset, depending on its version, but
+/// anything outside this list is rejected everywhere.
+pub const KNOWN_EVENTS: &[&str] = &[
+    "branch_protection_configuration",
+    "branch_protection_rule",
+    "check_run",
+    "check_suite",
+    "code_scanning_alert",
+    "commit_comment",
+    "create",
+    "custom_property",
+    "custom_property_values",
+    "delete",
+    "dependabot_alert",
+    "deploy_key",
+    "deployment",
+    "deployment_protection_rule",
+    "deployment_review",
+    "deployment_status",
+    "discussion",
+    "discussion_comment",
+    "enterprise",
+    "fork",
+    "github_app_authorization",
+    "gollum",
+    "installation",
+    "installation_repositories",
+    "installation_target",
+    "issue_comment",
+    "issues",
+    "label",
+    "marketplace_purchase",
+    "member",
+    "membership",
+    "merge_group",
+    "meta",
+    "milestone",
+    "org_block",
+    "organization",
+    "package",
+    "page_build",
+    "personal_access_token_request",
+    "ping",
+    "project",
+    "project_card",
+    "project_column",
+    "projects_v2",
+    "projects_v2_item",
+    "public",
+    "pull_request",
+    "pull_request_review",
+    "pull_request_review_comment",
+    "pull_request_review_thread",
+    "push",
+    "registry_package",
+    "release",
+    "repository",
+    "repository_advisory",
+    "repository_dispatch",
+    "repository_import",
+    "repository_ruleset",
+    "repository_vulnerability_alert",
+    "secret_scanning_alert",
+    "secret_scanning_alert_location",
+    "security_advisory",
+    "security_and_analysis",
+    "sponsorship",
+    "star",
+    "status",
+    "sub_issues",
+    "team",
+    "team_add",
+    "user",
+    "watch",
+    "workflow_dispatch",
+    "workflow_job",
+    "workflow_run",
+];
+
+/// Subscribes to every event
+pub const WILDCARD: &str = "*";
+
+/// What a webhook subscribes to when no events are given
+pub const DEFAULT_EVENT: &str = "push";
+
+/// The only events global webhooks can be triggered by, and what they subscribe to by default
+pub const GLOBAL_EVENTS: &[&str] = &["user", "organization"];
+
+/// What a webhook subscribes to when no events are given, a global one or any other
+pub fn defaults(global: bool) -> Vec<String> {
+    if global {
+        GLOBAL_EVENTS.iter().map(|event| event.to_string()).collect()
+    } else {
+        vec![DEFAULT_EVENT.to_string()]
+    }
+}
+
+/// Check event names against the catalog, so a typo fails up front with a suggestion instead of
+/// as a validation error from GitHub. Global webhooks only take `GLOBAL_EVENTS`.
+pub fn validate(events: &[String], global: bool) -> anyhow::Result<()> {
+    let unknown: Vec<String> = events.iter()
+        .filter(|event| event.as_str() != WILDCARD && !KNOWN_EVENTS.contains(&event.as_str()))
+        .map(|event| match suggest(event) {
+            Some(suggestion) => format!("`{}` (did you mean `{}`?)", event, suggestion),
+            None => format!("`{}`", event),
+        })
+        .collect();
+
+    if !unknown.is_empty() {
+        return Err(anyhow::anyhow!("Unknown event(s): {}", unknown.join(", ")));
+    }
+
+    let not_global: Vec<String> = events.iter()
+        .filter(|event| global && !GLOBAL_EVENTS.contains(&event.as_str()))
+        .map(|event| format!("`{}`", event))
+        .collect();
+    if !not_global.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Global webhooks only take `{}` events, not {}", GLOBAL_EVENTS.join("` and `"), not_global.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// The known event closest to a misspelled one, if any is close enough to be what was meant
+fn suggest(event: &str) -> Option<&'static str> {
+    KNOWN_EVENTS.iter()
+        .map(|known| (edit_distance(event, known), *known))
+        .filter(|(distance, known)| *distance <= 2.max(known.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{defaults, edit_distance, suggest, validate};
+
+    #[test]
+    fn test_validate_known_events() {
+        assert!(validate(&["push".to_string(), "pull_request".to_string()], false).is_ok());
+        assert!(validate(&["*".to_string()], false).is_ok());
+    }
+
+    #[test]
+    fn test_validate_global_events() {
+        assert!(validate(&["user".to_string(), "organization".to_string()], true).is_ok());
+        assert!(validate(&defaults(true), true).is_ok());
+        let error = validate(&["push".to_string(), "user".to_string()], true).unwrap_err();
+        assert_eq!(error.to_string(), "Global webhooks only take `user` and `organization` events, not `push`");
+        assert!(validate(&["*".to_string()], true).is_err());
+    }
+
+    #[test]
+    fn test_validate_suggests_close_event() {
+        let error = validate(&["pull_requests".to_string()], false).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown event(s): `pull_requests` (did you mean `pull_request`?)");
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("isues"), Some("issues"));
+        assert_eq!(suggest("workflow-run"), Some("workflow_run"));
+        assert_eq!(suggest("nonsense_event_name"), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("push", "push"), 0);
+        assert_eq!(edit_distance("push", "pull"), 2);
+        assert_eq!(edit_distance("", "star"), 4);
+    }
+}
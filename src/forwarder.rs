@@ -1,11 +1,15 @@
 use std::collections::HashMap;
 
+use anyhow::Context;
 use reqwest::header::HeaderName;
 
-use crate::gh::WebhookDeliveryRequest;
+use crate::{gh::WebhookDeliveryRequest, http::HttpConfig};
+
+/// Header naming the repository a delivery came from, added when forwarding from several at once
+pub const REPO_HEADER: &str = "X-GHES-Webhook-Repo";
 
 pub trait Forwarder {
-    fn forward(&self, payload: WebhookDeliveryRequest);
+    fn forward(&self, payload: WebhookDeliveryRequest) -> anyhow::Result<()>;
 }
 
 pub struct LocalForwarder {
@@ -14,38 +18,31 @@ pub struct LocalForwarder {
 }
 
 impl LocalForwarder {
-    pub fn new(url: String) -> Self {
+    pub fn new(url: String, http: &HttpConfig) -> anyhow::Result<Self> {
         let mut clean_url = url.trim().to_string();
-        if !clean_url.starts_with("http://") {
+        if !clean_url.starts_with("http://") && !clean_url.starts_with("https://") {
             clean_url = format!("http://{}", clean_url);
         }
-        LocalForwarder {
+        Ok(LocalForwarder {
             url: clean_url,
-            client: reqwest::blocking::Client::builder()
-                .user_agent(env!("CARGO_PKG_NAME"))
-                .build()
-                .expect("Failed to instantiate local forwarder client")
-        }
+            client: http.client().context("Failed to instantiate local forwarder client")?,
+        })
     }
 }
 
 impl Forwarder for LocalForwarder {
-    fn forward(&self, payload: WebhookDeliveryRequest) {
-        let result = self.client.post(&self.url)
+    fn forward(&self, payload: WebhookDeliveryRequest) -> anyhow::Result<()> {
+        let resp = self.client.post(&self.url)
             .json(&payload.payload)
             .headers(build_headers(payload.headers))
-            .send();
-
-        if let Err(e) = result {
-            log::warn!("Error forwarding payload: {:?}", e);
-            return;
-        } else {
-            let resp = result.unwrap();
-            log::info!("{} - {}", resp.status(), resp.text().unwrap());
-        }
+            .send()?;
+
+        log::info!("{} - {}", resp.status(), resp.text().unwrap_or_default());
+        Ok(())
     }
 }
 
+#[derive(Default)]
 pub struct StdOutForwarder;
 
 impl StdOutForwarder {
@@ -55,8 +52,9 @@ impl StdOutForwarder {
 }
 
 impl Forwarder for StdOutForwarder {
-    fn forward(&self, payload: WebhookDeliveryRequest) {
+    fn forward(&self, payload: WebhookDeliveryRequest) -> anyhow::Result<()> {
         log::info!("{}", payload);
+        Ok(())
     }
 }
 
@@ -1,109 +1,648 @@
-use std::{collections::HashMap, process::Command};
+use std::{collections::HashMap, path::PathBuf, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, thread, time::Duration};
 
+use anyhow::Context;
 use chrono::{DateTime, Utc};
+use reqwest::{blocking::{RequestBuilder, Response}, header::{HeaderMap, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LINK}, Method, StatusCode};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use which::which;
+
+use crate::{auth::{self, AppCredentials}, http::HttpConfig, ratelimit::{self, RateLimit}, retry::RetryPolicy};
+
+/// Secondary rate limits that ask us to back off for this long or less are waited out
+/// transparently, anything longer is surfaced as `GitHubError::RateLimited`
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How long to wait on each candidate when detecting the API URL
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How to reach a GitHub instance
+#[derive(Clone, Debug, Default)]
+pub struct GitHubConfig {
+    /// Host name, e.g. "github.com" or "github.example.com"
+    pub host: String,
+    /// Base URL of the REST API. Detected from the host when not set.
+    pub api_url: Option<String>,
+    /// File to read a token from when there isn't one in the environment or from `gh`
+    pub token_file: Option<PathBuf>,
+    /// TLS and proxy settings for talking to GitHub
+    pub http: HttpConfig,
+    /// How to retry requests that fail for transient reasons
+    pub retry: RetryPolicy,
+}
+
+/// A GitHub App has exactly one webhook, which isn't addressed by id. Clients created with
+/// `GitHub::new_with_app` accept any webhook id, use this one for clarity.
+pub const APP_WEBHOOK_ID: u32 = 0;
 
 #[derive(Clone)]
 pub struct GitHub {
+    /// Base URL of the REST API
+    api_url: String,
+    /// The hooks collection, or for a GitHub App its one and only hook
     url: String,
-    client: reqwest::blocking::Client
+    /// OAuth scopes that allow managing the hooks, any one of them will do
+    scopes: &'static [&'static str],
+    client: reqwest::blocking::Client,
+    rate_limit: Arc<Mutex<RateLimit>>,
+    app: Option<AppCredentials>,
+    /// Whether the hooks are global webhooks, which are created differently
+    global: bool,
+    /// Host name, normalized, used to look up its token
+    host: String,
+    token_file: Option<PathBuf>,
+    /// Resolved once and reused until GitHub rejects it
+    token: Arc<Mutex<Option<String>>>,
+    /// ETag of the last response for each conditionally requested URL
+    etags: Arc<Mutex<HashMap<String, String>>>,
+    /// How many conditional requests came back unchanged, for debugging
+    conditional_hits: Arc<AtomicU64>,
+    retry: RetryPolicy,
 }
 
 impl GitHub {
-    pub fn new_with_repo(host: String, repo: String) -> Self {
-        let url;
-        if host.contains("localhost") {
-            url = format!("http://{}/repos/{}/hooks", host, repo);
-        } else {
-            url = format!("https://api.{}/repos/{}/hooks", host, repo);
-        }
+    /// Client for the webhooks of a single repository, e.g. "org/repo"
+    pub fn new_with_repo(config: &GitHubConfig, repo: &str) -> anyhow::Result<Self> {
+        GitHub::new(config, &format!("repos/{}/hooks", repo), &["admin:repo_hook", "repo"])
+    }
 
+    /// Client for the webhooks of an organization, which receive events from all of its repositories
+    pub fn new_with_org(config: &GitHubConfig, org: &str) -> anyhow::Result<Self> {
+        GitHub::new(config, &format!("orgs/{}/hooks", org), &["admin:org_hook"])
+    }
+
+    /// Client for the global webhooks of a GHES instance, which receive enterprise level events
+    /// like `user` and `organization`. Requires a site admin token.
+    pub fn new_with_enterprise(config: &GitHubConfig) -> anyhow::Result<Self> {
+        Ok(GitHub {
+            global: true,
+            ..GitHub::new(config, "admin/hooks", &["site_admin", "admin:enterprise"])?
+        })
+    }
+
+    /// Client for a GitHub App's own webhook, authenticating as the app. The app's webhook always
+    /// exists, so it's only ever read from.
+    pub fn new_with_app(config: &GitHubConfig, credentials: AppCredentials) -> anyhow::Result<Self> {
+        Ok(GitHub {
+            app: Some(credentials),
+            ..GitHub::new(config, "app/hook", &[])?
+        })
+    }
+
+    /// Client for the webhooks of another repository on the same host. It shares the connection,
+    /// token and rate limit budget with this one, so polling several repositories costs one budget.
+    pub fn with_repo(&self, repo: &str) -> Self {
         GitHub {
-            url: url,
-            client: reqwest::blocking::Client::builder()
-                .user_agent(env!("CARGO_PKG_NAME"))
-                .build().unwrap()
+            url: format!("{}/repos/{}/hooks", self.api_url, repo),
+            scopes: &["admin:repo_hook", "repo"],
+            app: None,
+            global: false,
+            ..self.clone()
+        }
+    }
+
+    fn new(config: &GitHubConfig, hooks_path: &str, scopes: &'static [&'static str]) -> anyhow::Result<Self> {
+        let client = config.http.client().context("Failed to set up the GitHub client")?;
+        let api_url = match &config.api_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => resolve_api_url(&config.host, &client),
+        };
+        log::debug!("Using GitHub API at {}", api_url);
+
+        Ok(GitHub {
+            url: format!("{}/{}", api_url, hooks_path),
+            api_url,
+            scopes,
+            client,
+            rate_limit: Arc::new(Mutex::new(RateLimit::default())),
+            app: None,
+            global: false,
+            host: normalize_host(&config.host),
+            token_file: config.token_file.clone(),
+            token: Arc::new(Mutex::new(None)),
+            etags: Arc::new(Mutex::new(HashMap::new())),
+            conditional_hits: Arc::new(AtomicU64::new(0)),
+            retry: config.retry.clone(),
+        })
+    }
+
+    /// Make sure the token is allowed to manage the hooks before creating anything. Only classic
+    /// tokens list their scopes in `X-OAuth-Scopes`, fine-grained and app tokens aren't checked.
+    pub fn check_scopes(&self) -> anyhow::Result<()> {
+        if self.scopes.is_empty() {
+            return Ok(());
+        }
+
+        let resp = match self.send(self.client.get(format!("{}/", self.api_url))) {
+            Ok(resp) => resp,
+            Err(e) => {
+                log::debug!("Could not check the token's scopes: {:#}", e);
+                return Ok(());
+            }
+        };
+        let Some(header) = resp.headers().get("x-oauth-scopes").and_then(|v| v.to_str().ok()) else {
+            log::debug!("Token doesn't report its scopes, skipping the scope check");
+            return Ok(());
+        };
+
+        let granted = parse_scopes(header);
+        log::debug!("Token scopes: {}", granted.join(", "));
+        if self.scopes.iter().any(|scope| granted.iter().any(|g| g == scope)) {
+            Ok(())
+        } else {
+            Err(GitHubError::MissingScope { accepted: self.scopes, granted }.into())
         }
     }
 
-    pub fn create_webhook(&self, secret: Option<String>, events: Vec<String>) -> anyhow::Result<CreateWebhookResponse> {
-        let token = self.get_auth_token().unwrap();
+    pub fn create_webhook(&self, secret: Option<String>, events: Vec<String>) -> anyhow::Result<Webhook> {
         let body = CreateWebhookPayload {
-            name: "cli".to_string(),
+            name: if self.global { "web" } else { "cli" }.to_string(),
             active: true,
-            events: events,
+            events,
             config: WebhookConfig {
+                url: self.global.then(|| GLOBAL_HOOK_URL.to_string()),
                 content_type: "json".to_string(),
-                secret: secret,
+                secret,
+                marker: env!("CARGO_PKG_VERSION").to_string(),
             }
         };
 
-        let resp = self.client.post(&self.url)
-            .bearer_auth(token)
-            .json(&body)
-            .send()
-            .unwrap() // TODO handle error
-            .json::<CreateWebhookResponse>()
-            .unwrap(); // TODO handle error
+        let resp = self.send(self.client.post(&self.url).json(&body))?;
+        Ok(decode(resp)?)
+    }
+
+    pub fn list_webhooks(&self) -> anyhow::Result<Vec<Webhook>> {
+        Pages::new(self, format!("{}?per_page=100", self.url)).collect()
+    }
 
-        Ok(resp)
+    pub fn get_webhook(&self, webhook_id: u32) -> anyhow::Result<Webhook> {
+        let resp = self.send(self.client.get(self.hook_url(webhook_id)))?;
+        Ok(decode(resp)?)
     }
 
     pub fn delete_webhook(&self, webhook_id: u32) -> anyhow::Result<()> {
-        let token = self.get_auth_token().unwrap();
-        let url = format!("{}/{}", &self.url, webhook_id);
-        self.client.delete(&url)
-            .bearer_auth(token)
-            .send()
-            .unwrap(); // TODO handle error
+        self.send(self.client.delete(self.hook_url(webhook_id)))?;
+        Ok(())
+    }
 
+    /// Ask GitHub to send a `ping` event to the webhook
+    pub fn ping_webhook(&self, webhook_id: u32) -> anyhow::Result<()> {
+        self.send(self.client.post(format!("{}/pings", self.hook_url(webhook_id))))?;
         Ok(())
     }
 
-    pub fn get_webhook_deliveries(&self, id: u32) -> anyhow::Result<Vec<WebhookDelivery>> {
-        let token = self.get_auth_token().unwrap();
-        let url = format!("{}/{}/deliveries?per_page=100", &self.url, id);
-        let resp = self.client.get(&url)
-            .bearer_auth(token)
-            .send()
-            .unwrap() // TODO handle error;
-            .json::<Vec<WebhookDelivery>>()
-            .unwrap(); // TODO handle error
+    /// Iterate over a webhook's deliveries, newest first. Pages are only requested as the
+    /// iterator reaches them, so stopping early avoids fetching history we've already seen.
+    /// The first page is requested conditionally, so if nothing was delivered since the last
+    /// call, this yields nothing without costing any rate limit.
+    pub fn get_webhook_deliveries(&self, id: u32) -> Deliveries<'_> {
+        Pages::new(self, format!("{}/deliveries?per_page=100", self.hook_url(id))).conditional()
+    }
 
-        Ok(resp)
+    fn get_page<T: DeserializeOwned>(&self, url: &str, conditional: bool) -> anyhow::Result<Page<T>> {
+        let mut request = self.client.get(url);
+        if conditional {
+            if let Some(etag) = self.etags.lock().unwrap().get(url) {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+        }
+
+        let resp = self.send(request)?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            let hits = self.conditional_hits.fetch_add(1, Ordering::Relaxed) + 1;
+            log::debug!("{} has not changed ({} conditional hits so far)", url, hits);
+            return Ok(Page { items: Vec::new(), next: None });
+        }
+        if conditional {
+            if let Some(etag) = resp.headers().get(ETAG).and_then(|v| v.to_str().ok()) {
+                self.etags.lock().unwrap().insert(url.to_string(), etag.to_string());
+            }
+        }
+
+        let next = resp.headers().get(LINK)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_next_link);
+        let items = decode(resp)?;
+
+        Ok(Page { items, next })
     }
 
     pub fn get_webhook_delivery_details(&self, webhook_id: u32, delivery_id: u64) -> anyhow::Result<WebhookDeliveryDetails> {
-        let token = self.get_auth_token().unwrap();
-        let url = format!("{}/{}/deliveries/{}", &self.url, webhook_id, delivery_id);
-        let resp = self.client.get(&url)
-            .bearer_auth(token)
-            .send()
-            .unwrap() // TODO handle error
-            .json::<WebhookDeliveryDetails>()
-            .unwrap(); // TODO handle error
+        let url = format!("{}/deliveries/{}", self.hook_url(webhook_id), delivery_id);
+        let resp = self.send(self.client.get(&url))?;
+        Ok(decode(resp)?)
+    }
+
+    fn hook_url(&self, webhook_id: u32) -> String {
+        match self.app {
+            Some(_) => self.url.clone(),
+            None => format!("{}/{}", self.url, webhook_id),
+        }
+    }
+
+    /// Ask GitHub to send a delivery again. The new attempt shows up as a new delivery.
+    pub fn redeliver(&self, webhook_id: u32, delivery_id: u64) -> anyhow::Result<()> {
+        let url = format!("{}/deliveries/{}/attempts", self.hook_url(webhook_id), delivery_id);
+        self.send(self.client.post(url))?;
+        Ok(())
+    }
+
+    /// URL of the hooks collection, or the app's hook. Identifies where deliveries come from.
+    pub fn location(&self) -> &str {
+        &self.url
+    }
+
+    /// Last known rate limit budget
+    pub fn rate_limit(&self) -> RateLimit {
+        self.rate_limit.lock().unwrap().clone()
+    }
+
+    /// Authenticate and send a request, keeping track of the rate limit along the way. Requests
+    /// are paced when the budget runs low, and short secondary rate limits are waited out. If the
+    /// token is rejected it's resolved again once, in case it was refreshed since we cached it.
+    /// Transient failures are retried with backoff, as long as repeating the request is safe.
+    fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        let mut waited_out_rate_limit = false;
+        let mut refreshed_token = false;
+        let mut backoff = self.retry.backoff();
+        let mut attempts = 0;
+        let idempotent = request.try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| matches!(*r.method(), Method::GET | Method::HEAD | Method::DELETE));
+
+        loop {
+            self.throttle()?;
+            attempts += 1;
+
+            let attempt = request.try_clone().context("Request can't be retried")?
+                .bearer_auth(self.get_auth_token()?);
+            let result = attempt.send()
+                .map_err(GitHubError::Transport)
+                .and_then(|resp| {
+                    let mut rate_limit = self.rate_limit.lock().unwrap();
+                    rate_limit.update(resp.headers());
+                    log::debug!("Rate limit: {}", rate_limit);
+                    drop(rate_limit);
+                    check_status(resp)
+                });
+
+            match result {
+                Err(e) if e.is_retryable(idempotent) => match backoff.next_delay() {
+                    Some(delay) => {
+                        log::warn!("{}, retrying in {:.1} seconds", e, delay.as_secs_f64());
+                        thread::sleep(delay);
+                    }
+                    None if attempts > 1 => return Err(e).context(format!("Giving up after {} attempts", attempts)),
+                    None => return Err(e.into()),
+                },
+                Err(GitHubError::RateLimited { reset_at: Some(reset_at), .. }) if !waited_out_rate_limit && wait_time(reset_at) <= MAX_RETRY_AFTER => {
+                    let wait = wait_time(reset_at);
+                    log::warn!("Hit a secondary rate limit, retrying in {} seconds", wait.as_secs());
+                    thread::sleep(wait);
+                    waited_out_rate_limit = true;
+                }
+                Err(GitHubError::Unauthorized(_)) if !refreshed_token && self.app.is_none() => {
+                    log::debug!("Token was rejected, resolving it again");
+                    self.token.lock().unwrap().take();
+                    refreshed_token = true;
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// Refuse to spend requests we know will be rate limited, and slow down when the budget is low
+    fn throttle(&self) -> Result<(), GitHubError> {
+        let rate_limit = self.rate_limit();
+        let now = Utc::now();
+
+        if rate_limit.is_exhausted(now) {
+            return Err(GitHubError::RateLimited {
+                error: ApiError {
+                    message: Some("Rate limit exhausted".to_string()),
+                    ..Default::default()
+                },
+                reset_at: rate_limit.reset_at,
+            });
+        }
+
+        if let Some(delay) = rate_limit.pacing_delay(now) {
+            log::debug!("Rate limit budget is low ({}), waiting {:?} before the next request", rate_limit, delay);
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    fn get_auth_token(&self) -> anyhow::Result<String> {
+        if let Some(app) = &self.app {
+            return app.jwt();
+        }
 
-        Ok(resp)
+        let mut token = self.token.lock().unwrap();
+        if let Some(token) = token.as_ref() {
+            return Ok(token.clone());
+        }
+        let resolved = auth::resolve_token(&self.host, self.token_file.as_deref())?;
+        *token = Some(resolved.clone());
+        Ok(resolved)
     }
+}
 
-    fn get_auth_token(&self) -> Result<String, String> {
-        let cmd = which("gh").unwrap(); // gh has to be installed, otherwise how are you running this?
-        let output = Command::new(cmd)
-            .args(["auth", "token"])
-            .output();
+fn normalize_host(host: &str) -> String {
+    host.trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
+        .to_lowercase()
+}
+
+/// Figure out where the REST API lives for a host. github.com and GHE.com (data residency) use an
+/// `api.` subdomain. GHES serves it from `/api/v3`, or from an `api.` subdomain with subdomain
+/// isolation enabled, so for those we ask `/meta` which one answers.
+fn resolve_api_url(host: &str, client: &reqwest::blocking::Client) -> String {
+    let candidates = candidate_api_urls(host);
+    if candidates.len() == 1 {
+        return candidates.into_iter().next().unwrap();
+    }
 
-        match output {
-            Ok(res) => return Ok(String::from_utf8(res.stdout).unwrap().trim_ascii().to_string()),
-            Err(_) => return Err("Failed to get auth token".into()),
+    for candidate in &candidates {
+        let url = format!("{}/meta", candidate);
+        log::debug!("Probing {}", url);
+        match client.get(&url).timeout(PROBE_TIMEOUT).send() {
+            Ok(resp) if is_api_response(&resp) => return candidate.clone(),
+            Ok(resp) => log::debug!("{} is not the API: HTTP {}", url, resp.status()),
+            Err(e) => log::debug!("{} is not the API: {}", url, e),
         }
     }
+
+    log::warn!("Could not detect the API for {}, falling back to {}. Use --api-url to set it explicitly.", host, candidates[0]);
+    candidates.into_iter().next().unwrap()
+}
+
+/// Possible REST API base URLs for a host, most likely first
+fn candidate_api_urls(host: &str) -> Vec<String> {
+    let host = normalize_host(host);
+
+    if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
+        vec![format!("http://{}", host)]
+    } else if host == "github.com" || host == "www.github.com" {
+        vec!["https://api.github.com".to_string()]
+    } else if host.ends_with(".ghe.com") {
+        vec![format!("https://api.{}", host)]
+    } else {
+        vec![format!("https://{}/api/v3", host), format!("https://api.{}", host)]
+    }
 }
 
+/// `/meta` answers with JSON, unless the instance is in private mode, where anonymous requests get a JSON 401
+fn is_api_response(resp: &Response) -> bool {
+    let is_json = resp.headers().get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    is_json && (resp.status().is_success() || resp.status() == StatusCode::UNAUTHORIZED)
+}
+
+fn wait_time(until: DateTime<Utc>) -> Duration {
+    until.signed_duration_since(Utc::now()).to_std().unwrap_or_default()
+}
+
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+}
+
+/// Iterator over a list endpoint that follows the `Link` header from page to page.
+/// Yields an error at most once, after which it stops.
+pub struct Pages<'a, T> {
+    gh: &'a GitHub,
+    next_url: Option<String>,
+    page: std::vec::IntoIter<T>,
+    /// Whether the next page should be requested with `If-None-Match`. Only ever the first one.
+    conditional: bool,
+    /// The first page's URL when it was requested conditionally. Its ETag is forgotten if a later
+    /// page fails, otherwise the next walk would stop at the unchanged first page and never get
+    /// to the pages this one missed.
+    etag_url: Option<String>,
+}
+
+pub type Deliveries<'a> = Pages<'a, WebhookDelivery>;
+
+impl<'a, T> Pages<'a, T> {
+    fn new(gh: &'a GitHub, url: String) -> Self {
+        Pages {
+            gh,
+            next_url: Some(url),
+            page: Vec::new().into_iter(),
+            conditional: false,
+            etag_url: None,
+        }
+    }
+
+    fn conditional(self) -> Self {
+        Pages { conditional: true, etag_url: self.next_url.clone(), ..self }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for Pages<'_, T> {
+    type Item = anyhow::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.page.next() {
+                return Some(Ok(item));
+            }
+
+            let url = self.next_url.take()?;
+            log::debug!("Fetching page: {}", url);
+            let conditional = std::mem::take(&mut self.conditional);
+            match self.gh.get_page(&url, conditional) {
+                Ok(page) => {
+                    self.next_url = page.next;
+                    self.page = page.items.into_iter();
+                }
+                Err(e) => {
+                    if let Some(etag_url) = self.etag_url.take() {
+                        self.gh.etags.lock().unwrap().remove(&etag_url);
+                    }
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+fn parse_scopes(header: &str) -> Vec<String> {
+    header.split(',')
+        .map(|scope| scope.trim().to_string())
+        .filter(|scope| !scope.is_empty())
+        .collect()
+}
+
+/// Pull the `rel="next"` URL out of a `Link` header
+fn parse_next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let is_next = params.split(';').any(|p| p.trim() == "rel=\"next\"");
+        is_next.then(|| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+/// Body GitHub returns alongside an unsuccessful status code
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: u16,
+    pub message: Option<String>,
+    pub documentation_url: Option<String>,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}", self.status)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if let Some(url) = &self.documentation_url {
+            write!(f, " (see {})", url)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum GitHubError {
+    /// The request never produced a response (DNS, TLS, connection reset, timeout...)
+    Transport(reqwest::Error),
+    /// 401 - the token is missing, expired, or not valid for this host
+    Unauthorized(ApiError),
+    /// 404 - the resource doesn't exist, or the token can't see it
+    NotFound(ApiError),
+    /// 403 - the token is valid but lacks the required permission
+    PermissionDenied(ApiError),
+    /// 429, or a 403 caused by an exhausted rate limit. `reset_at` is when it's worth trying again, if GitHub said.
+    RateLimited { error: ApiError, reset_at: Option<DateTime<Utc>> },
+    /// Any other unsuccessful status code
+    Status(ApiError),
+    /// The token's scopes, as reported by GitHub, don't include any that would allow managing the hooks
+    MissingScope { accepted: &'static [&'static str], granted: Vec<String> },
+    /// The response was successful, but the body wasn't what we expected
+    Decode { status: u16, source: serde_json::Error },
+}
+
+impl GitHubError {
+    /// Whether the failure is likely to go away by itself: the connection dropped or timed out, or
+    /// a proxy or load balancer in front of GitHub answered for it. Requests that aren't
+    /// idempotent are only retried if they never made it to the server.
+    pub fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            GitHubError::Transport(e) if e.is_connect() => true,
+            GitHubError::Transport(e) => idempotent && (e.is_timeout() || e.is_request() || e.is_body()),
+            GitHubError::Status(e) => idempotent && matches!(e.status, 502..=504),
+            _ => false,
+        }
+    }
+
+    /// Suggestion for the user on how to resolve the error
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            GitHubError::Transport(_) => Some("Check that --github-host is correct and the host is reachable. Behind a proxy or an internal CA? See --proxy and --ca-cert"),
+            GitHubError::Unauthorized(_) => Some("Run `gh auth login -h <host>` (or check GH_TOKEN/GH_ENTERPRISE_TOKEN) and try again"),
+            GitHubError::NotFound(_) => Some("Check the repository or organization name, and that your account has admin access to it"),
+            GitHubError::PermissionDenied(_) | GitHubError::MissingScope { .. } => Some("Your token needs the `admin:repo_hook` scope (`admin:org_hook` for organizations, `site_admin` for global webhooks). Run `gh auth refresh -s admin:repo_hook,admin:org_hook`"),
+            GitHubError::RateLimited { .. } => Some("Wait for the rate limit to reset and try again"),
+            GitHubError::Status(_) => None,
+            GitHubError::Decode { .. } => Some("The host may not be a GitHub API endpoint. Check that --github-host is correct"),
+        }
+    }
+}
+
+impl std::fmt::Display for GitHubError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitHubError::Transport(_) => write!(f, "Failed to reach GitHub"),
+            GitHubError::Unauthorized(e) => write!(f, "Authentication failed - {}", e),
+            GitHubError::NotFound(e) => write!(f, "Not found - {}", e),
+            GitHubError::PermissionDenied(e) => write!(f, "Permission denied - {}", e),
+            GitHubError::RateLimited { error, reset_at: Some(reset_at) } => write!(f, "Rate limited until {} - {}", reset_at.to_rfc3339(), error),
+            GitHubError::RateLimited { error, reset_at: None } => write!(f, "Rate limited - {}", error),
+            GitHubError::Status(e) => write!(f, "Request failed - {}", e),
+            GitHubError::MissingScope { accepted, granted } if granted.is_empty() => write!(f, "Token has none of the required scopes ({}), it has no scopes at all", accepted.join(" or ")),
+            GitHubError::MissingScope { accepted, granted } => write!(f, "Token has none of the required scopes ({}), only {}", accepted.join(" or "), granted.join(", ")),
+            GitHubError::Decode { status, .. } => write!(f, "Unexpected response body (HTTP {})", status),
+        }
+    }
+}
+
+impl std::error::Error for GitHubError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GitHubError::Transport(e) => Some(e),
+            GitHubError::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Map an unsuccessful response to a `GitHubError`, passing successful ones through
+fn check_status(resp: Response) -> Result<Response, GitHubError> {
+    let status = resp.status();
+    // 304 only comes back for conditional requests, where it's the answer we were hoping for
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        return Ok(resp);
+    }
+
+    let headers = resp.headers().clone();
+    let body = resp.text().unwrap_or_default();
+    let error = classify_error(status, &headers, &body);
+    log::debug!("GitHub returned an error: {}", error);
+    Err(error)
+}
+
+fn classify_error(status: StatusCode, headers: &HeaderMap, body: &str) -> GitHubError {
+    let mut error = serde_json::from_str::<ApiError>(body).unwrap_or_default();
+    error.status = status.as_u16();
+    let reset_at = ratelimit::retry_at(headers, Utc::now());
+
+    match status {
+        StatusCode::UNAUTHORIZED => GitHubError::Unauthorized(error),
+        StatusCode::NOT_FOUND => GitHubError::NotFound(error),
+        StatusCode::TOO_MANY_REQUESTS => GitHubError::RateLimited { error, reset_at },
+        StatusCode::FORBIDDEN => {
+            let mentions_rate_limit = error.message.as_ref()
+                .is_some_and(|m| m.to_lowercase().contains("rate limit"));
+            if reset_at.is_some() || mentions_rate_limit {
+                GitHubError::RateLimited { error, reset_at }
+            } else {
+                GitHubError::PermissionDenied(error)
+            }
+        }
+        _ => GitHubError::Status(error),
+    }
+}
+
+/// Decode the JSON body of a successful response
+fn decode<T: DeserializeOwned>(resp: Response) -> Result<T, GitHubError> {
+    let status = resp.status().as_u16();
+    let body = resp.bytes().map_err(GitHubError::Transport)?;
+    serde_json::from_slice(&body).map_err(|source| GitHubError::Decode { status, source })
+}
+
+/// Key added to the config of every webhook this tool creates, so leftovers can be told apart
+/// from webhooks that belong to someone else
+pub const MARKER_KEY: &str = "gh_ghes_webhook";
+
+/// Global webhooks can't be `cli` hooks, they have to be `web` hooks with a URL to deliver to.
+/// Deliveries are only ever polled, so they're sent to a name that never resolves (RFC 2606).
+const GLOBAL_HOOK_URL: &str = "https://gh-ghes-webhook.invalid/";
+
 #[derive(serde::Serialize, Debug)]
 struct WebhookConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
     content_type: String,
     secret: Option<String>,
+    /// Version of the tool that created the webhook
+    #[serde(rename = "gh_ghes_webhook")]
+    marker: String,
 }
 #[derive(serde::Serialize, Debug)]
 struct CreateWebhookPayload {
@@ -114,19 +653,42 @@ struct CreateWebhookPayload {
 }
 
 #[derive(serde::Deserialize, Debug)]
-pub struct CreateWebhookResponse {
+pub struct Webhook {
     pub id: u32,
     pub name: String,
     pub active: bool,
     pub events: Vec<String>,
+    #[serde(default)]
+    pub config: HashMap<String, Value>,
+    pub created_at: Option<DateTime<Utc>>,
 }
 
-#[derive(serde::Deserialize, Debug)]
+impl Webhook {
+    /// Whether this tool created the webhook, going by the marker in its config
+    pub fn is_cli_created(&self) -> bool {
+        self.config.contains_key(MARKER_KEY)
+    }
+
+    /// Whether the webhook looks like one this tool created, in case GitHub dropped the marker,
+    /// which isn't a documented config key. `cli` hooks without a URL are created by other CLI
+    /// tools too, like `gh webhook forward`, and may well be in use.
+    pub fn looks_cli_created(&self) -> bool {
+        let url = self.config.get("url").and_then(Value::as_str).filter(|url| !url.is_empty());
+        (self.name == "cli" && url.is_none()) || url == Some(GLOBAL_HOOK_URL)
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
 pub struct WebhookDelivery {
     pub id: u64,
+    /// Shared by every attempt to deliver the same event
+    pub guid: Option<String>,
     pub delivered_at: DateTime<Utc>,
+    /// Whether this attempt is a redelivery of an earlier one
+    #[serde(default)]
+    pub redelivery: bool,
     pub event: String,
-    pub action: String,
+    pub action: Option<String>,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -137,9 +699,12 @@ pub struct WebhookDeliveryRequest {
 #[derive(serde::Deserialize, Debug)]
 pub struct WebhookDeliveryDetails {
     pub id: u64,
+    pub guid: Option<String>,
     pub delivered_at: DateTime<Utc>,
+    #[serde(default)]
+    pub redelivery: bool,
     pub event: String,
-    pub action: String,
+    pub action: Option<String>,
     pub request: WebhookDeliveryRequest,
 }
 
@@ -151,4 +716,110 @@ impl std::fmt::Display for WebhookDeliveryRequest {
             .join("\n");
         write!(f, "{}\n\n{}", headers, self.payload)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use reqwest::{header::HeaderMap, StatusCode};
+
+    use super::{candidate_api_urls, classify_error, parse_next_link, parse_scopes, GitHubError, Webhook};
+
+    fn webhook(name: &str, config: serde_json::Value) -> Webhook {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": name,
+            "active": true,
+            "events": ["push"],
+            "config": config,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_is_cli_created() {
+        assert!(webhook("cli", serde_json::json!({"content_type": "json", "gh_ghes_webhook": "1.1.0"})).is_cli_created());
+        assert!(!webhook("cli", serde_json::json!({"content_type": "json"})).is_cli_created());
+        assert!(!webhook("web", serde_json::json!({"url": "https://gh-ghes-webhook.invalid/"})).is_cli_created());
+    }
+
+    #[test]
+    fn test_looks_cli_created() {
+        assert!(webhook("cli", serde_json::json!({"content_type": "json"})).looks_cli_created());
+        assert!(webhook("web", serde_json::json!({"url": "https://gh-ghes-webhook.invalid/"})).looks_cli_created());
+        assert!(!webhook("web", serde_json::json!({"url": "https://ci.example.com/hook", "content_type": "json"})).looks_cli_created());
+        assert!(!webhook("cli", serde_json::json!({"url": "https://ci.example.com/hook"})).looks_cli_created());
+    }
+
+    #[test]
+    fn test_classify_error() {
+        let body = r#"{"message": "Not Found", "documentation_url": "https://docs.github.com"}"#;
+        match classify_error(StatusCode::NOT_FOUND, &HeaderMap::new(), body) {
+            GitHubError::NotFound(e) => {
+                assert_eq!(e.status, 404);
+                assert_eq!(e.message.as_deref(), Some("Not Found"));
+                assert_eq!(e.documentation_url.as_deref(), Some("https://docs.github.com"));
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_classify_forbidden() {
+        let body = r#"{"message": "Resource not accessible by personal access token"}"#;
+        assert!(matches!(classify_error(StatusCode::FORBIDDEN, &HeaderMap::new(), body), GitHubError::PermissionDenied(_)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "30".parse().unwrap());
+        assert!(matches!(
+            classify_error(StatusCode::FORBIDDEN, &headers, body),
+            GitHubError::RateLimited { reset_at: Some(_), .. }
+        ));
+
+        let body = r#"{"message": "API rate limit exceeded for user ID 1."}"#;
+        assert!(matches!(
+            classify_error(StatusCode::FORBIDDEN, &HeaderMap::new(), body),
+            GitHubError::RateLimited { reset_at: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_classify_html_body() {
+        match classify_error(StatusCode::BAD_GATEWAY, &HeaderMap::new(), "<html>Bad Gateway</html>") {
+            GitHubError::Status(e) => {
+                assert_eq!(e.status, 502);
+                assert!(e.message.is_none());
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_parse_next_link() {
+        let header = r#"<https://api.github.com/repositories/1/hooks/2/deliveries?per_page=100&cursor=v1_123>; rel="next", <https://api.github.com/repositories/1/hooks/2/deliveries?per_page=100>; rel="first""#;
+        assert_eq!(
+            parse_next_link(header).as_deref(),
+            Some("https://api.github.com/repositories/1/hooks/2/deliveries?per_page=100&cursor=v1_123")
+        );
+    }
+
+    #[test]
+    fn test_parse_last_page_link() {
+        let header = r#"<https://api.github.com/repositories/1/hooks/2/deliveries?per_page=100>; rel="first""#;
+        assert!(parse_next_link(header).is_none());
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(parse_scopes("repo, admin:org_hook, gist"), vec!["repo", "admin:org_hook", "gist"]);
+        assert!(parse_scopes("").is_empty());
+    }
+
+    #[test]
+    fn test_candidate_api_urls() {
+        assert_eq!(candidate_api_urls("github.com"), vec!["https://api.github.com"]);
+        assert_eq!(candidate_api_urls("octocorp.ghe.com"), vec!["https://api.octocorp.ghe.com"]);
+        assert_eq!(candidate_api_urls("localhost:3000"), vec!["http://localhost:3000"]);
+        assert_eq!(
+            candidate_api_urls("https://GitHub.Example.com/"),
+            vec!["https://github.example.com/api/v3", "https://api.github.example.com"]
+        );
+    }
+}
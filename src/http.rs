@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use reqwest::{blocking::Client, Certificate, Proxy};
+
+/// TLS and proxy settings for an HTTP client. Proxies from `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY`
+/// are used unless a proxy is given explicitly or turned off.
+#[derive(Clone, Debug, Default)]
+pub struct HttpConfig {
+    /// PEM file with extra root certificates to trust. Falls back to `SSL_CERT_FILE`.
+    pub ca_cert: Option<PathBuf>,
+    /// Proxy URL for all requests, overriding the environment
+    pub proxy: Option<String>,
+    /// Ignore proxies from the environment
+    pub no_proxy: bool,
+}
+
+impl HttpConfig {
+    pub fn client(&self) -> anyhow::Result<Client> {
+        let mut builder = Client::builder().user_agent(env!("CARGO_PKG_NAME"));
+
+        let ca_cert = self.ca_cert.clone()
+            .or_else(|| std::env::var_os("SSL_CERT_FILE").map(PathBuf::from));
+        if let Some(path) = ca_cert {
+            for certificate in load_certificates(&path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy).with_context(|| format!("Invalid proxy URL {}", proxy))?);
+        } else if self.no_proxy {
+            builder = builder.no_proxy();
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+fn load_certificates(path: &Path) -> anyhow::Result<Vec<Certificate>> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed to read CA certificates from {}", path.display()))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("{} is not a PEM certificate bundle", path.display()))?;
+    log::debug!("Trusting {} certificate(s) from {}", certificates.len(), path.display());
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::HttpConfig;
+
+    #[test]
+    fn test_client_with_ca_cert() {
+        let config = HttpConfig {
+            ca_cert: Some(PathBuf::from("tests/fixtures/ca.pem")),
+            ..Default::default()
+        };
+        assert!(config.client().is_ok());
+    }
+
+    #[test]
+    fn test_client_with_missing_ca_cert() {
+        let config = HttpConfig {
+            ca_cert: Some(PathBuf::from("tests/fixtures/missing.pem")),
+            ..Default::default()
+        };
+        let error = config.client().unwrap_err();
+        assert!(error.to_string().contains("Failed to read CA certificates from tests/fixtures/missing.pem"));
+    }
+
+    #[test]
+    fn test_client_with_invalid_proxy() {
+        let config = HttpConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(config.client().is_err());
+    }
+}
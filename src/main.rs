@@ -1,12 +1,25 @@
-use std::{process::exit, sync::mpsc, thread};
+use std::{io::{self, IsTerminal, Write}, path::{Path, PathBuf}, process::exit, sync::{mpsc::{self, RecvTimeoutError}, Arc, Mutex}, thread, time::{Duration, Instant}};
 
-use clap::{Args, Parser, Subcommand};
+use auth::AppCredentials;
+use chrono::{DateTime, SubsecRound, Utc};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use gh::GitHub;
+use http::HttpConfig;
 use simplelog::{ConfigBuilder, TermLogger};
 
+pub mod auth;
+pub mod dedupe;
+pub mod events;
 pub mod gh;
+pub mod http;
 pub mod pollster;
 pub mod forwarder;
+pub mod ratelimit;
+pub mod retry;
+pub mod state;
+
+/// `ping` is short lived and waiting on a single delivery, so it polls much faster than `forward`
+const PING_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Parser)]
 #[command(version, about = "Webhook forwarding for GitHub Enterprise Server", long_about = None)]
@@ -22,38 +35,354 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Forward webhooks to a local process
-    Forward {
-        /// Names of the event types to forward. Use `*` to forward all events default: push
-        #[arg(short='E', long, num_args=1.., value_delimiter=',')]
-        events: Vec<String>,
+    Forward(ForwardArgs),
+
+    /// Delete webhooks left behind by sessions that didn't shut down cleanly
+    Cleanup(CleanupArgs),
+
+    /// Send a ping through the whole pipeline and report how long each step took
+    Ping(PingArgs),
+
+    /// Ask GitHub to send past deliveries again, so a running `forward` session picks them up
+    Redeliver(RedeliverArgs),
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("replay").args(["since", "resume"])))]
+struct ForwardArgs {
+    /// Names of the event types to forward. Use `*` to forward all events (default: push, or user and organization with --enterprise)
+    #[arg(short='E', long, num_args=1.., value_delimiter=',')]
+    events: Vec<String>,
+
+    #[command(flatten)]
+    host: HostArgs,
+
+    #[command(flatten)]
+    location: WebhookLocation,
 
-        /// GitHub host name (default "github.com")
-        #[arg(short='H', long, default_value_t=String::from("github.com"))]
-        github_host: String,
+    /// ID of an existing webhook to forward deliveries from, instead of creating a temporary one. It's left untouched.
+    #[arg(long, conflicts_with="app_id")]
+    webhook_id: Option<u32>,
 
-        #[command(flatten)]
-        location: WebhookLocation,
+    /// Path to the GitHub App's private key, used with --app-id
+    #[arg(long, requires="app_id")]
+    private_key: Option<PathBuf>,
 
-        /// Webhook secret for incoming events
-        #[arg(short='S', long)]
-        secret: Option<String>,
+    /// Webhook secret for incoming events
+    #[arg(short='S', long)]
+    secret: Option<String>,
 
-        /// Address of the local server to receive events. If omitted, events will be printed to stdout
-        #[arg(short='U', long)]
-        url: Option<String>,
-    },
+    #[command(flatten)]
+    target: TargetArgs,
+
+    #[command(flatten)]
+    poll: PollArgs,
+
+    /// Pick up where the last --resume session for the same location left off: reattach to its
+    /// webhook and forward everything delivered since. The webhook is kept when the session ends.
+    #[arg(long, default_value_t=false)]
+    resume: bool,
+
+    /// Replay deliveries made since then before forwarding new ones, oldest first. A timestamp like
+    /// 2024-08-01T09:00:00Z, or how long ago, e.g. 1h
+    #[arg(long, value_parser=parse_since, conflicts_with="resume")]
+    since: Option<DateTime<Utc>>,
+
+    /// Replay at most this many of the deliveries made before the session started, the most recent
+    /// ones. Needs --since or --resume
+    #[arg(long, requires="replay")]
+    limit: Option<usize>,
+}
+
+/// How often to poll GitHub, and for how long
+#[derive(Args)]
+struct PollArgs {
+    /// Time between polls right after a delivery, e.g. 10s or 1m. At least 2s (default: 5s for repositories, 15s otherwise)
+    #[arg(long, value_parser=parse_interval)]
+    interval: Option<Duration>,
+
+    /// Longest time between polls. While nothing is delivered, the interval grows toward this. Set it to --interval for a fixed cadence
+    #[arg(long, value_parser=parse_interval, default_value="1m")]
+    max_interval: Duration,
+
+    /// Poll less often as the rate limit budget drops below half, leaving room for other tools using the same token
+    #[arg(long, default_value_t=false)]
+    weigh_rate_limit: bool,
+
+    /// Stop after this long without any deliveries, e.g. 30m or 2h. 0 keeps polling forever
+    #[arg(long, value_parser=parse_duration, default_value="10m")]
+    idle_timeout: Duration,
+
+    /// Don't shut down at the idle timeout, poll every few minutes instead until deliveries pick up again
+    #[arg(long, default_value_t=false)]
+    keep_alive: bool,
+
+    /// Which attempts to forward when GitHub delivers an event more than once, e.g. after a redelivery
+    #[arg(long, value_enum, default_value_t=dedupe::Dedupe::All)]
+    dedupe: dedupe::Dedupe,
+
+    /// How many delivery details to fetch from GitHub at once when several deliveries come in together
+    #[arg(long, default_value_t=pollster::DEFAULT_FETCH_CONCURRENCY, value_parser=parse_concurrency)]
+    fetch_concurrency: usize,
+}
+
+impl PollArgs {
+    fn config(&self, default_interval: Duration) -> pollster::PollConfig {
+        pollster::PollConfig {
+            interval: self.interval.unwrap_or(default_interval),
+            max_interval: self.max_interval,
+            weigh_by_rate_limit: self.weigh_rate_limit,
+            idle_timeout: Some(self.idle_timeout).filter(|timeout| !timeout.is_zero()),
+            keep_alive: self.keep_alive,
+            fetch_concurrency: self.fetch_concurrency,
+            catch_up_limit: None,
+            dedupe: self.dedupe,
+        }
+    }
+}
+
+#[derive(Args)]
+struct CleanupArgs {
+    #[command(flatten)]
+    host: HostArgs,
+
+    #[command(flatten)]
+    location: HookLocation,
+
+    /// Delete without asking for confirmation
+    #[arg(short='y', long, default_value_t=false)]
+    yes: bool,
+
+    /// Also delete webhooks without this extension's marker that look like it created them: `cli`
+    /// webhooks without a URL, which `gh webhook forward` creates too, and global webhooks pointing
+    /// nowhere. Check the list before confirming, another tool may still be using them.
+    #[arg(long, default_value_t=false)]
+    include_unmarked: bool,
+}
+
+#[derive(Args)]
+struct PingArgs {
+    #[command(flatten)]
+    host: HostArgs,
+
+    #[command(flatten)]
+    location: HookLocation,
+
+    /// ID of an existing webhook to ping, instead of creating a temporary one
+    #[arg(long)]
+    webhook_id: Option<u32>,
+
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Seconds to wait for the ping to come through before giving up
+    #[arg(long, default_value_t=60)]
+    timeout: u64,
+}
+
+#[derive(Args)]
+struct RedeliverArgs {
+    #[command(flatten)]
+    host: HostArgs,
+
+    #[command(flatten)]
+    location: WebhookLocation,
+
+    /// ID of the webhook the deliveries belong to
+    #[arg(long, required_unless_present="app_id")]
+    webhook_id: Option<u32>,
+
+    /// Path to the GitHub App's private key, used with --app-id
+    #[arg(long, requires="app_id")]
+    private_key: Option<PathBuf>,
+
+    /// IDs of the deliveries to send again
+    #[arg(required=true, num_args=1..)]
+    delivery_ids: Vec<u64>,
+}
+
+#[derive(Args)]
+struct HostArgs {
+    /// GitHub host name (default "github.com")
+    #[arg(short='H', long, default_value_t=String::from("github.com"))]
+    github_host: String,
+
+    /// Base URL of the REST API, e.g. https://github.example.com/api/v3. Detected from the host if omitted
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// File containing a token, used when none is set in the environment or available from `gh auth token`
+    #[arg(long)]
+    token_file: Option<PathBuf>,
+
+    /// PEM file with CA certificates to trust for GitHub, e.g. an internal CA. Defaults to $SSL_CERT_FILE
+    #[arg(long)]
+    ca_cert: Option<PathBuf>,
+
+    /// Proxy for requests to GitHub. Defaults to $HTTPS_PROXY
+    #[arg(long, conflicts_with="no_proxy")]
+    proxy: Option<String>,
+
+    /// Connect to GitHub directly, ignoring proxies from the environment
+    #[arg(long, default_value_t=false)]
+    no_proxy: bool,
+
+    /// How long to keep retrying GitHub API requests that fail for transient reasons (dropped
+    /// connections, timeouts, 502/503/504), e.g. 30s or 5m. 0 turns retries off
+    #[arg(long, value_parser=parse_duration, default_value="2m")]
+    retry_max_elapsed: Duration,
+}
+
+impl HostArgs {
+    fn config(&self) -> gh::GitHubConfig {
+        gh::GitHubConfig {
+            host: self.github_host.clone(),
+            api_url: self.api_url.clone(),
+            token_file: self.token_file.clone(),
+            http: HttpConfig {
+                ca_cert: self.ca_cert.clone(),
+                proxy: self.proxy.clone(),
+                no_proxy: self.no_proxy,
+            },
+            retry: retry::RetryPolicy {
+                max_elapsed: self.retry_max_elapsed,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Where deliveries get forwarded to
+#[derive(Args)]
+struct TargetArgs {
+    /// Address of the local server to receive events. If omitted, events will be printed to stdout
+    #[arg(short='U', long)]
+    url: Option<String>,
+
+    /// PEM file with CA certificates to trust for --url. Defaults to $SSL_CERT_FILE
+    #[arg(long, requires="url")]
+    forward_ca_cert: Option<PathBuf>,
+
+    /// Proxy for requests to --url. Defaults to $HTTPS_PROXY/$HTTP_PROXY
+    #[arg(long, requires="url", conflicts_with="forward_no_proxy")]
+    forward_proxy: Option<String>,
+
+    /// Connect to --url directly, ignoring proxies from the environment
+    #[arg(long, requires="url", default_value_t=false)]
+    forward_no_proxy: bool,
+}
+
+impl TargetArgs {
+    fn forwarder(self) -> Box<dyn forwarder::Forwarder> {
+        let Some(url) = self.url else {
+            return Box::new(forwarder::StdOutForwarder::new());
+        };
+
+        let http = HttpConfig {
+            ca_cert: self.forward_ca_cert,
+            proxy: self.forward_proxy,
+            no_proxy: self.forward_no_proxy,
+        };
+        match forwarder::LocalForwarder::new(url, &http) {
+            Ok(forwarder) => Box::new(forwarder),
+            Err(e) => {
+                report_error("Failed to set up forwarding", &e);
+                exit(1);
+            }
+        }
+    }
 }
 
 #[derive(Args)]
 #[group(required = true, multiple=false)]
 struct WebhookLocation {
-        /// NOT SUPPORTED - organization where the webhook is installed
+        /// Name of the organization where the webhook is installed
+        #[arg(short='O', long)]
+        org: Option<String>,
+
+        /// Name of the repo where the webhook is installed. Repeat it, or separate names with commas, to
+        /// forward from several repositories at once
+        #[arg(short='R', long, value_delimiter=',')]
+        repo: Vec<String>,
+
+        /// Use a global webhook for the whole GHES instance (requires site admin)
+        #[arg(long, default_value_t=false)]
+        enterprise: bool,
+
+        /// ID of a GitHub App whose own webhook deliveries should be forwarded. Nothing is created or deleted.
+        #[arg(long, requires="private_key")]
+        app_id: Option<u64>,
+}
+
+impl WebhookLocation {
+    fn client(&self, config: &gh::GitHubConfig, private_key: Option<&Path>) -> GitHub {
+        let gh = match (&self.org, self.repo.first(), self.app_id) {
+            (Some(org), _, _) => GitHub::new_with_org(config, org),
+            (_, Some(repo), _) => GitHub::new_with_repo(config, repo),
+            (_, _, Some(app_id)) => AppCredentials::from_file(app_id, private_key.expect("clap requires --private-key"))
+                .and_then(|credentials| GitHub::new_with_app(config, credentials)),
+            _ => GitHub::new_with_enterprise(config),
+        };
+        unwrap_client(gh)
+    }
+
+    /// A client for each repository, sharing one token and rate limit budget, or the one client for
+    /// anywhere else. Each comes with the name of its repository when there's more than one.
+    fn clients(&self, config: &gh::GitHubConfig, private_key: Option<&Path>) -> Vec<(GitHub, Option<String>)> {
+        let gh = self.client(config, private_key);
+        if self.repo.len() < 2 {
+            return vec![(gh, None)];
+        }
+        self.repo.iter()
+            .map(|repo| (gh.with_repo(repo), Some(repo.clone())))
+            .collect()
+    }
+
+    fn poll_interval(&self) -> Duration {
+        if !self.repo.is_empty() {
+            pollster::REPO_POLL_INTERVAL
+        } else {
+            pollster::ORG_POLL_INTERVAL
+        }
+    }
+}
+
+/// Places this tool can create webhooks in
+#[derive(Args)]
+#[group(required = true, multiple=false)]
+struct HookLocation {
+        /// Name of the organization where the webhook is installed
         #[arg(short='O', long)]
         org: Option<String>,
 
         /// Name of the repo where the webhook is installed
         #[arg(short='R', long)]
         repo: Option<String>,
+
+        /// Use the global webhooks of the GHES instance (requires site admin)
+        #[arg(long, default_value_t=false)]
+        enterprise: bool,
+}
+
+impl HookLocation {
+    fn client(&self, config: &gh::GitHubConfig) -> GitHub {
+        let gh = match (&self.org, &self.repo) {
+            (Some(org), _) => GitHub::new_with_org(config, org),
+            (_, Some(repo)) => GitHub::new_with_repo(config, repo),
+            _ => GitHub::new_with_enterprise(config),
+        };
+        unwrap_client(gh)
+    }
+}
+
+fn unwrap_client(gh: anyhow::Result<GitHub>) -> GitHub {
+    match gh {
+        Ok(gh) => gh,
+        Err(e) => {
+            report_error("Failed to set up GitHub client", &e);
+            exit(1);
+        }
+    }
 }
 
 fn main() {
@@ -61,53 +390,225 @@ fn main() {
     configure_logger(cli.debug);
 
     match cli.command {
-        Commands::Forward {events, github_host, location, secret, url} => {
-            let gh = match location.org {
-                Some(_) => {
-                    log::error!("Organization webhooks are not supported.");
+        Commands::Forward(args) => forward(args),
+        Commands::Cleanup(args) => cleanup(args),
+        Commands::Ping(args) => ping(args),
+        Commands::Redeliver(args) => redeliver(args),
+    }
+}
+
+fn forward(args: ForwardArgs) {
+    let ForwardArgs {events, host, location, webhook_id, private_key, secret, target, poll, resume, since, limit} = args;
+    let config = host.config();
+
+    if webhook_id.is_some() && location.repo.len() > 1 {
+        log::error!("--webhook-id can only be used with a single --repo");
+        exit(1);
+    }
+
+    let creates_webhook = location.app_id.is_none() && webhook_id.is_none();
+    if creates_webhook {
+        if let Err(e) = events::validate(&events, location.enterprise) {
+            report_error("Invalid --events", &e);
+            exit(1);
+        }
+    }
+    if location.app_id.is_some() {
+        if !events.is_empty() || secret.is_some() {
+            log::warn!("--events and --secret are ignored for GitHub Apps, they come from the app's settings");
+        }
+        log::info!("Polling GitHub App webhook deliveries");
+    } else if webhook_id.is_some() && (!events.is_empty() || secret.is_some()) {
+        log::warn!("--events and --secret are ignored when attaching to an existing webhook");
+    }
+    let events = if events.is_empty() { events::defaults(location.enterprise) } else { events };
+
+    let poll_config = pollster::PollConfig {
+        catch_up_limit: limit,
+        ..poll.config(location.poll_interval())
+    };
+    if let Some(since) = since {
+        log::info!("Replaying deliveries since {}", since.to_rfc3339());
+    }
+
+    let state = resume.then(open_state_dir);
+    // Hooks are added as they're created, so one interrupted while setting up the next still gets deleted
+    let hooks = Arc::new(Mutex::new(Vec::new()));
+    set_ctrlc_listener(&hooks);
+    let mut sources = Vec::new();
+    for (gh, repo) in location.clients(&config, private_key.as_deref()) {
+        let for_repo = repo.as_ref().map(|repo| format!(" for {}", repo)).unwrap_or_default();
+        let mut resumed = state.as_ref().and_then(|state| saved_cursor(state, &gh, webhook_id));
+        if let Some(cursor) = &resumed {
+            if creates_webhook && !webhook_exists(&gh, cursor.webhook_id) {
+                log::warn!("Webhook {} from the last session is gone, creating a new one", cursor.webhook_id);
+                resumed = None;
+            }
+        }
+
+        // The webhook we created (or reattached to), which has to be cleaned up when we're done
+        let created_webhook = if location.app_id.is_some() {
+            None
+        } else if let Some(webhook_id) = webhook_id {
+            attach_webhook(&gh, webhook_id);
+            None
+        } else if let Some(cursor) = &resumed {
+            log::info!("Reattached to webhook {}{}", cursor.webhook_id, for_repo);
+            Some(cursor.webhook_id)
+        } else {
+            check_scopes(&gh);
+            sweep_stale_webhooks(&gh);
+            match gh.create_webhook(secret.clone(), events.clone()) {
+                Ok(webhook) => {
+                    log::info!("CLI Webhook created{}", for_repo);
+                    Some(webhook.id)
+                }
+                Err(e) => {
+                    report_error(&format!("Failed to create CLI webhook{}", for_repo), &e);
+                    shut_down(&hooks.lock().unwrap());
                     exit(1);
                 }
-                None => gh::GitHub::new_with_repo(github_host, location.repo.unwrap()),
-            };
-
-            let webhook = gh.create_webhook(secret, events).unwrap();
-            let webhook_id = webhook.id;
-            log::info!("CLI Webhook created");
-
-            // Set up a handler to delete the webhook when the user presses Ctrl-C
-            set_ctrlc_listener(&gh, webhook_id);
-
-            // spawn thread to poll for events
-            let (tx, rx) = mpsc::channel();
-            let gh_clone = gh.clone();
-            thread::spawn(move || {
-                pollster::poll(tx, &gh_clone, &webhook);
-            });
-
-            // forward events
-            let forwarder: Box<dyn forwarder::Forwarder> = match url {
-                Some(u) => Box::new(forwarder::LocalForwarder::new(u)),
-                None => Box::new(forwarder::StdOutForwarder::new())
-            };
-            loop {
-                if let Ok(details) = rx.recv() {
-                    match details {
-                        pollster::PollMessage::TimedOut => {
-                            log::warn!("Polling timed out after 10 minutes of inactivity. Shutting down...");
-                            gh.delete_webhook(webhook_id).unwrap();
-                            break;
-                        }
-                        pollster::PollMessage::Delivery(details) => {
-                            log::info!("Forwarding event: {}", details.id);
-                            forwarder.forward(details.request);
-                        }
-                    }
+            }
+        };
+        let webhook_id = created_webhook.or(webhook_id).unwrap_or(gh::APP_WEBHOOK_ID);
+
+        let resuming = resumed.is_some();
+        let start = resumed.or_else(|| since.map(|since| {
+            state::Cursor { webhook_id, last_delivery_id: None, timestamp: since, seen_guids: Default::default() }
+        }));
+        let mut cursor = start.clone().unwrap_or_else(|| state::Cursor {
+            webhook_id,
+            last_delivery_id: None,
+            timestamp: Utc::now().trunc_subsecs(0),
+            seen_guids: Default::default(),
+        });
+
+        // With --resume the webhook has to outlive the session, so the next one has something to reattach to
+        let created_webhook = match &state {
+            Some(state) => {
+                if let Some(created_webhook) = created_webhook {
+                    log::info!("Webhook {}{} is kept when this session ends, run `cleanup` to remove it", created_webhook, for_repo);
+                }
+                if !resuming {
+                    save_cursor(state, &gh, &cursor);
+                }
+                None
+            }
+            None => created_webhook,
+        };
+
+        let seen_guids = Arc::new(Mutex::new(std::mem::take(&mut cursor.seen_guids)));
+        let retrying = Arc::new(Mutex::new(Vec::new()));
+        hooks.lock().unwrap().push(Hook {
+            gh: gh.clone(),
+            webhook_id,
+            created_webhook,
+            repo,
+            cursor,
+            seen_guids: seen_guids.clone(),
+            retrying: retrying.clone(),
+            missed: Vec::new(),
+            failed_forward: None,
+        });
+        sources.push(pollster::Source { gh, webhook_id, resume_from: start, seen_guids, retrying });
+    }
+
+    // spawn thread to poll for events
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        pollster::poll(tx, sources, &poll_config);
+    });
+
+    // forward events
+    let forwarder = target.forwarder();
+    loop {
+        match rx.recv() {
+            Ok(pollster::PollMessage::TimedOut) => {
+                log::warn!("Polling timed out after {} of inactivity. Shutting down...", format_duration(poll.idle_timeout));
+                shut_down(&hooks.lock().unwrap());
+                break;
+            }
+            Ok(pollster::PollMessage::Missed { source, delivery_id, error }) => {
+                report_error(&format!("Giving up on delivery {}", delivery_id), &error);
+                hooks.lock().unwrap()[source].missed.push(delivery_id);
+            }
+            Ok(pollster::PollMessage::Delivery { source, mut details }) => {
+                // the Ctrl-C handler needs the hooks too, so don't hold them while the handler takes its time
+                let repo = hooks.lock().unwrap()[source].repo.clone();
+                let from_repo = repo.as_ref().map(|repo| format!(" from {}", repo)).unwrap_or_default();
+                log::info!("Forwarding event: {}{}{}", details.id, from_repo, if details.redelivery { " (redelivery)" } else { "" });
+                if let Some(repo) = repo {
+                    details.request.headers.insert(forwarder::REPO_HEADER.to_string(), repo);
                 }
+                let (delivery_id, delivered_at, guid) = (details.id, details.delivered_at, details.guid);
+                if let Err(e) = forwarder.forward(details.request) {
+                    log::warn!("Error forwarding payload: {:?}", e);
+                    let hook = &mut hooks.lock().unwrap()[source];
+                    hook.missed.push(delivery_id);
+                    hook.failed_forward = Some(hook.failed_forward.map_or(delivery_id, |id| id.min(delivery_id)));
+                    continue;
+                }
+
+                let mut hooks = hooks.lock().unwrap();
+                let hook = &mut hooks[source];
+                if let Some(guid) = &guid {
+                    hook.seen_guids.lock().unwrap().insert(guid);
+                }
+                // a delivery that had to be fetched again can come in after newer ones, the cursor
+                // only ever moves forward so those aren't forwarded again on --resume. It doesn't
+                // move past one that failed to forward either, so --resume tries that again.
+                let below_failed = hook.failed_forward.is_none_or(|id| delivery_id < id);
+                if below_failed && hook.cursor.last_delivery_id.is_none_or(|id| delivery_id > id) {
+                    hook.cursor.last_delivery_id = Some(delivery_id);
+                    hook.cursor.timestamp = delivered_at;
+                }
+                if let Some(state) = &state {
+                    let cursor = state::Cursor { seen_guids: hook.seen_guids.lock().unwrap().clone(), ..hook.cursor.clone() };
+                    save_cursor(state, &hook.gh, &cursor);
+                }
+            }
+            Ok(pollster::PollMessage::Failed(e)) => {
+                report_error("Polling failed", &e);
+                if location.enterprise && matches!(e.downcast_ref::<gh::GitHubError>(), Some(gh::GitHubError::NotFound(_))) {
+                    // listing a global webhook's deliveries isn't documented, not every version may have it
+                    log::error!("This GHES instance doesn't list the deliveries of global webhooks, which --enterprise relies on");
+                }
+                log::error!("Shutting down...");
+                shut_down(&hooks.lock().unwrap());
+                exit(1);
+            }
+            Err(_) => {
+                // the poller hung up without saying why, which shouldn't happen
+                log::error!("Polling stopped unexpectedly. Shutting down...");
+                shut_down(&hooks.lock().unwrap());
+                exit(1);
             }
         }
     }
 }
 
+/// A webhook `forward` is polling, and what's left to do with it when the session ends
+struct Hook {
+    gh: GitHub,
+    webhook_id: u32,
+    /// Set if we created the webhook and it has to be deleted at the end
+    created_webhook: Option<u32>,
+    /// The repository it belongs to, when forwarding from several
+    repo: Option<String>,
+    /// Where this session is at, saved for --resume along with `seen_guids`
+    cursor: state::Cursor,
+    /// Events already forwarded, shared with the poller so `--dedupe first` skips their other attempts
+    seen_guids: Arc<Mutex<dedupe::SeenGuids>>,
+    /// Deliveries the poller is still trying to fetch, shared with it so they're listed if the session
+    /// is stopped before it gets to them
+    retrying: Arc<Mutex<Vec<u64>>>,
+    /// Deliveries that couldn't be fetched or forwarded, listed at shutdown. They can only be
+    /// redelivered if the webhook is still around by then.
+    missed: Vec<u64>,
+    /// The oldest delivery that couldn't be forwarded, the cursor stays below it
+    failed_forward: Option<u64>,
+}
+
 fn configure_logger(verbose: bool) {
     TermLogger::init(
         if verbose { simplelog::LevelFilter::Debug } else { simplelog::LevelFilter::Info },
@@ -120,15 +621,424 @@ fn configure_logger(verbose: bool) {
     ).unwrap();
 }
 
-fn set_ctrlc_listener(gh: &GitHub, webhook_id: u32) {
-    let gh_clone = gh.clone();
+fn ping(args: PingArgs) {
+    let gh = args.location.client(&args.host.config());
+    let forwarder = args.target.forwarder();
+    let started = Instant::now();
+    let mut stage = Instant::now();
+    let mut report = |name: &str| {
+        log::info!("{:<28} {:>10.2?} (total {:.2?})", name, stage.elapsed(), started.elapsed());
+        stage = Instant::now();
+    };
+
+    let created_webhook = match args.webhook_id {
+        Some(webhook_id) => {
+            attach_webhook(&gh, webhook_id);
+            report("Webhook found");
+            None
+        }
+        None => {
+            check_scopes(&gh);
+            match gh.create_webhook(None, events::defaults(args.location.enterprise)) {
+                Ok(webhook) => {
+                    report("Webhook created");
+                    Some(webhook.id)
+                }
+                Err(e) => {
+                    report_error("Failed to create CLI webhook", &e);
+                    exit(1);
+                }
+            }
+        }
+    };
+    let webhook_id = created_webhook.or(args.webhook_id).unwrap();
+    set_ctrlc_listener(&Arc::new(Mutex::new(vec![Hook {
+        gh: gh.clone(),
+        webhook_id,
+        created_webhook,
+        repo: None,
+        cursor: state::Cursor {
+            webhook_id,
+            last_delivery_id: None,
+            timestamp: Utc::now(),
+            seen_guids: Default::default(),
+        },
+        seen_guids: Default::default(),
+        retrying: Default::default(),
+        missed: Default::default(),
+        failed_forward: None,
+    }])));
+
+    // start polling before pinging, so the delivery can't slip in before the poller's start time
+    let (tx, rx) = mpsc::channel();
+    let source = pollster::Source { gh: gh.clone(), webhook_id, resume_from: None, seen_guids: Default::default(), retrying: Default::default() };
+    thread::spawn(move || {
+        let poll_config = pollster::PollConfig {
+            interval: PING_POLL_INTERVAL,
+            max_interval: PING_POLL_INTERVAL,
+            weigh_by_rate_limit: false,
+            idle_timeout: None,
+            keep_alive: false,
+            fetch_concurrency: 1,
+            catch_up_limit: None,
+            dedupe: dedupe::Dedupe::All,
+        };
+        pollster::poll(tx, vec![source], &poll_config);
+    });
+
+    let ping_sent_at = Utc::now();
+    if let Err(e) = gh.ping_webhook(webhook_id) {
+        report_error("Failed to ping webhook", &e);
+        delete_webhook(&gh, created_webhook);
+        exit(1);
+    }
+    report("Ping sent");
+
+    let deadline = Instant::now() + Duration::from_secs(args.timeout);
+    let succeeded = loop {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(pollster::PollMessage::Delivery { details, .. }) if is_requested_ping(&details, ping_sent_at) => {
+                let recorded_after = details.delivered_at.signed_duration_since(ping_sent_at).to_std().unwrap_or_default();
+                log::info!("{:<28} {:>10.2?} after the ping was sent", "Delivery recorded by GitHub", recorded_after);
+                report("Delivery details fetched");
+
+                match forwarder.forward(details.request) {
+                    Ok(()) => {
+                        report("Delivery forwarded");
+                        break true;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to forward the ping: {:?}", e);
+                        break false;
+                    }
+                }
+            }
+            Ok(pollster::PollMessage::Delivery { details, .. }) => {
+                log::debug!("Skipping {} delivery {} while waiting for the ping", details.event, details.id);
+            }
+            Ok(pollster::PollMessage::TimedOut) | Err(RecvTimeoutError::Timeout) => {
+                log::error!("No ping delivery showed up within {} seconds", args.timeout);
+                break false;
+            }
+            Ok(pollster::PollMessage::Failed(e)) => {
+                report_error("Polling failed before the ping showed up", &e);
+                break false;
+            }
+            Ok(pollster::PollMessage::Missed { delivery_id, error, .. }) => {
+                log::warn!("Could not fetch the details of delivery {}: {:#}", delivery_id, error);
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                log::error!("Polling stopped before the ping showed up");
+                break false;
+            }
+        }
+    };
+
+    delete_webhook(&gh, created_webhook);
+    exit(if succeeded { 0 } else { 1 });
+}
+
+fn redeliver(args: RedeliverArgs) {
+    if args.location.repo.len() > 1 {
+        log::error!("Deliveries can only be redelivered for one --repo at a time");
+        exit(1);
+    }
+    let gh = args.location.client(&args.host.config(), args.private_key.as_deref());
+    let webhook_id = args.webhook_id.unwrap_or(gh::APP_WEBHOOK_ID);
+
+    let mut all_requested = true;
+    for delivery_id in args.delivery_ids {
+        match gh.redeliver(webhook_id, delivery_id) {
+            Ok(()) => log::info!("Requested redelivery of {}", delivery_id),
+            Err(e) => {
+                report_error(&format!("Failed to redeliver {}", delivery_id), &e);
+                all_requested = false;
+            }
+        }
+    }
+    if !all_requested {
+        exit(1);
+    }
+}
+
+fn cleanup(args: CleanupArgs) {
+    let gh = args.location.client(&args.host.config());
+    let stale = match gh.list_webhooks() {
+        Ok(webhooks) => webhooks.into_iter()
+            .filter(|w| w.is_cli_created() || (args.include_unmarked && w.looks_cli_created()))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            report_error("Failed to list webhooks", &e);
+            exit(1);
+        }
+    };
+
+    if stale.is_empty() {
+        log::info!("No webhooks created by {} found", env!("CARGO_PKG_NAME"));
+        return;
+    }
+    log_webhooks(&stale);
+
+    if !args.yes && !confirm(&format!("Delete {} webhook(s)?", stale.len())) {
+        log::info!("Nothing was deleted, pass --yes to delete without confirmation");
+        return;
+    }
+    if !delete_webhooks(&gh, &stale) {
+        exit(1);
+    }
+}
+
+/// Offer to delete webhooks earlier sessions left behind, before adding another one. A failure to
+/// list them shouldn't get in the way of forwarding.
+fn sweep_stale_webhooks(gh: &GitHub) {
+    let stale = match gh.list_webhooks() {
+        Ok(webhooks) => webhooks.into_iter().filter(|w| w.is_cli_created()).collect::<Vec<_>>(),
+        Err(e) => {
+            log::debug!("Could not check for stale webhooks: {:#}", e);
+            return;
+        }
+    };
+    if stale.is_empty() {
+        return;
+    }
+
+    log::warn!("Found {} webhook(s) left behind by previous sessions", stale.len());
+    log_webhooks(&stale);
+    if confirm("Delete them? Another session may still be using them.") {
+        delete_webhooks(gh, &stale);
+    } else {
+        log::info!("Run `gh ghes-webhook cleanup` to remove them later");
+    }
+}
+
+fn log_webhooks(webhooks: &[gh::Webhook]) {
+    for webhook in webhooks {
+        let created_at = webhook.created_at.map(|t| t.to_rfc3339()).unwrap_or_else(|| "unknown".to_string());
+        log::info!("  {} - created {}, events: {}", webhook.id, created_at, webhook.events.join(", "));
+    }
+}
+
+/// Delete each webhook, returning whether they all went
+fn delete_webhooks(gh: &GitHub, webhooks: &[gh::Webhook]) -> bool {
+    let mut all_deleted = true;
+    for webhook in webhooks {
+        match gh.delete_webhook(webhook.id) {
+            Ok(()) => log::info!("Deleted webhook {}", webhook.id),
+            Err(e) => {
+                report_error(&format!("Failed to delete webhook {}", webhook.id), &e);
+                all_deleted = false;
+            }
+        }
+    }
+    all_deleted
+}
+
+/// Ask a yes/no question on the terminal. Anything but "y" is a no, and so is not having a terminal to ask on.
+fn confirm(question: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+
+    print!("{} [y/N] ", question);
+    io::stdout().flush().ok();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Make sure an existing webhook is there before polling it, and show what it's subscribed to
+fn attach_webhook(gh: &GitHub, webhook_id: u32) {
+    match gh.get_webhook(webhook_id) {
+        Ok(webhook) => {
+            log::info!("Attached to webhook {} ({}), events: {}", webhook.id, webhook.name, webhook.events.join(", "));
+            if !webhook.active {
+                log::warn!("Webhook {} is not active, GitHub won't deliver anything to it", webhook.id);
+            }
+        }
+        Err(e) => {
+            report_error(&format!("Failed to find webhook {}", webhook_id), &e);
+            exit(1);
+        }
+    }
+}
+
+fn open_state_dir() -> state::StateDir {
+    match state::StateDir::in_config_dir() {
+        Ok(state) => state,
+        Err(e) => {
+            report_error("Can't keep track of the session for --resume", &e);
+            exit(1);
+        }
+    }
+}
+
+/// The cursor saved by the last session for the same location, if it can be used for this one
+fn saved_cursor(state: &state::StateDir, gh: &GitHub, webhook_id: Option<u32>) -> Option<state::Cursor> {
+    let cursor = match state.cursor(gh.location()) {
+        Ok(Some(cursor)) => cursor,
+        Ok(None) => {
+            log::info!("No previous session to resume, starting a new one");
+            return None;
+        }
+        Err(e) => {
+            report_error("Failed to read the previous session, starting a new one", &e);
+            return None;
+        }
+    };
+
+    if webhook_id.is_some_and(|id| id != cursor.webhook_id) {
+        log::info!("The previous session used webhook {}, starting a new one", cursor.webhook_id);
+        return None;
+    }
+    match cursor.last_delivery_id {
+        Some(id) => log::info!("Resuming after delivery {} ({})", id, cursor.timestamp.to_rfc3339()),
+        None => log::info!("Resuming from {}", cursor.timestamp.to_rfc3339()),
+    }
+    Some(cursor)
+}
+
+fn save_cursor(state: &state::StateDir, gh: &GitHub, cursor: &state::Cursor) {
+    if let Err(e) = state.save_cursor(gh.location(), cursor) {
+        log::warn!("Failed to save the session to {}: {:#}", state.path(gh.location()).display(), e);
+    }
+}
+
+fn webhook_exists(gh: &GitHub, webhook_id: u32) -> bool {
+    match gh.get_webhook(webhook_id) {
+        Ok(_) => true,
+        Err(e) if matches!(e.downcast_ref::<gh::GitHubError>(), Some(gh::GitHubError::NotFound(_))) => false,
+        Err(e) => {
+            report_error(&format!("Failed to find webhook {}", webhook_id), &e);
+            exit(1);
+        }
+    }
+}
+
+fn set_ctrlc_listener(hooks: &Arc<Mutex<Vec<Hook>>>) {
+    let hooks = hooks.clone();
     ctrlc::set_handler(move || {
-        log::info!("Deleting CLI webhook");
-        gh_clone.delete_webhook(webhook_id).unwrap();
+        shut_down(&hooks.lock().unwrap());
         std::process::exit(0);
     }).unwrap();
 }
 
+/// Report what was missed and delete the webhooks we created
+fn shut_down(hooks: &[Hook]) {
+    for hook in hooks {
+        report_missed(hook);
+        delete_webhook(&hook.gh, hook.created_webhook);
+    }
+}
+
+/// List the deliveries that were never forwarded, so they can be redelivered
+fn report_missed(hook: &Hook) {
+    let missed: Vec<u64> = hook.missed.iter().chain(hook.retrying.lock().unwrap().iter()).copied().collect();
+    if missed.is_empty() {
+        return;
+    }
+
+    let from_repo = hook.repo.as_ref().map(|repo| format!(" from {}", repo)).unwrap_or_default();
+    let ids = missed.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" ");
+    log::error!("Permanently missed {} delivery(s){}: {}", missed.len(), from_repo, ids);
+    if hook.created_webhook.is_none() {
+        log::error!("Run `gh ghes-webhook redeliver` for webhook {} with these ids to get them again", hook.webhook_id);
+    }
+}
+
+/// Delete the webhook if we created it, otherwise there's nothing to clean up
+fn delete_webhook(gh: &GitHub, created_webhook: Option<u32>) {
+    let Some(webhook_id) = created_webhook else {
+        return;
+    };
+
+    log::info!("Deleting CLI webhook");
+    if let Err(e) = gh.delete_webhook(webhook_id) {
+        report_error(&format!("Failed to delete CLI webhook {}, it may need to be removed manually", webhook_id), &e);
+    }
+}
+
+/// Whether a delivery is the ping `ping` asked for, sent no earlier than `ping_sent_at`. GitHub also
+/// pings a webhook when it's created, and that one shouldn't be mistaken for ours. Deliveries only
+/// have whole seconds, so one made in the same second as ours can't be told apart.
+fn is_requested_ping(details: &gh::WebhookDeliveryDetails, ping_sent_at: DateTime<Utc>) -> bool {
+    details.event == "ping" && details.delivered_at >= ping_sent_at.trunc_subsecs(0)
+}
+
+/// Parse a duration like "500ms", "30s", "5m" or "1h". A bare number is taken as seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("`{}` is not a duration, e.g. 30s or 5m", s))?;
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 60 * 60)),
+        unit => Err(format!("Unknown unit `{}` in `{}`, use ms, s, m or h", unit, s)),
+    }
+}
+
+/// Parse an RFC 3339 timestamp, or a duration meaning that long ago
+fn parse_since(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let ago = parse_duration(s).map_err(|_| format!("`{}` is neither a timestamp like 2024-08-01T09:00:00Z nor a duration like 1h", s))?;
+    chrono::Duration::from_std(ago)
+        .ok()
+        .and_then(|ago| Utc::now().checked_sub_signed(ago))
+        .ok_or_else(|| format!("`{}` is too long ago", s))
+}
+
+/// Format a duration the way `parse_duration` reads it, in the largest unit that's exact
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if duration.subsec_millis() != 0 || secs == 0 {
+        format!("{}ms", duration.as_millis())
+    } else if secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let interval = parse_duration(s)?;
+    if interval < pollster::MIN_POLL_INTERVAL {
+        return Err(format!("must be at least {} to go easy on the rate limit", format_duration(pollster::MIN_POLL_INTERVAL)));
+    }
+    Ok(interval)
+}
+
+fn parse_concurrency(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("`{}` is not a number", s)),
+    }
+}
+
+/// Stop before creating anything if the token can't manage webhooks
+fn check_scopes(gh: &GitHub) {
+    if let Err(e) = gh.check_scopes() {
+        report_error("Can't manage webhooks with this token", &e);
+        exit(1);
+    }
+}
+
+/// Log an error, along with a suggestion on how to fix it when GitHub returned something we understand
+pub fn report_error(context: &str, e: &anyhow::Error) {
+    log::error!("{}: {:#}", context, e);
+    if let Some(hint) = e.downcast_ref::<gh::GitHubError>().and_then(|e| e.hint()) {
+        log::error!("{}", hint);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +1048,58 @@ mod tests {
     fn verify_cli() {
         Cli::command().debug_assert();
     }
+
+    fn ping_delivery(delivered_at: &str) -> gh::WebhookDeliveryDetails {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "delivered_at": delivered_at,
+            "event": "ping",
+            "request": {"headers": {}, "payload": {}},
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_is_requested_ping() {
+        let ping_sent_at = DateTime::parse_from_rfc3339("2024-08-01T09:00:05.600Z").unwrap().with_timezone(&Utc);
+        assert!(is_requested_ping(&ping_delivery("2024-08-01T09:00:05Z"), ping_sent_at));
+        assert!(is_requested_ping(&ping_delivery("2024-08-01T09:00:06Z"), ping_sent_at));
+        // GitHub's own ping from when the webhook was created
+        assert!(!is_requested_ping(&ping_delivery("2024-08-01T09:00:04Z"), ping_sent_at));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("2024-08-01T09:00:00Z").unwrap().to_rfc3339(), "2024-08-01T09:00:00+00:00");
+        assert_eq!(parse_since("2024-08-01T11:00:00+02:00").unwrap().to_rfc3339(), "2024-08-01T09:00:00+00:00");
+        let an_hour_ago = Utc::now() - chrono::Duration::hours(1);
+        assert!((parse_since("1h").unwrap() - an_hour_ago).num_seconds().abs() <= 1);
+        assert!(parse_since("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(600)), "10m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("10s"), Ok(Duration::from_secs(10)));
+        assert!(parse_interval("500ms").is_err());
+        assert!(parse_interval("0").is_err());
+    }
 }
\ No newline at end of file
@@ -1,89 +1,544 @@
-use std::{sync::mpsc::Sender, thread, time::Duration};
-use chrono::{DateTime, Utc};
+use std::{collections::BTreeMap, sync::{atomic::{AtomicUsize, Ordering}, mpsc::{self, Sender}, Arc, Mutex}, thread, time::{Duration, Instant}};
+use chrono::{DateTime, SubsecRound, Utc};
 
-use crate::gh::{CreateWebhookResponse, GitHub, WebhookDelivery, WebhookDeliveryDetails};
+use crate::{dedupe::{self, Dedupe, SeenGuids}, gh::{GitHub, GitHubError, WebhookDelivery, WebhookDeliveryDetails}, ratelimit::RateLimit, state::Cursor};
+
+/// Default time between polls for a repository webhook
+pub const REPO_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Organization and global webhooks see events from every repository, and their rate limit tends to
+/// be shared across a lot of tooling, so they're polled less aggressively by default
+pub const ORG_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Polling faster than this would eat through the rate limit for little gain
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How much the interval grows after each poll that found nothing
+const IDLE_BACKOFF_FACTOR: f64 = 1.5;
+
+/// Once less than this share of the rate limit is left, polling slows down in proportion,
+/// if weighing by the rate limit is turned on
+const BUDGET_WEIGHT_THRESHOLD: f64 = 0.5;
+
+/// Default time without deliveries after which polling stops
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Time between polls in keep-alive mode once idle past the timeout
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How many delivery details are fetched at once by default
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 4;
+
+/// How many times to try fetching a delivery's details before giving up on it
+const DETAIL_FETCH_ATTEMPTS: u32 = 5;
+
+/// Wait before fetching details again after the first failure, doubled after each one after that
+const DETAIL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How long to back off when GitHub rate limits us without saying for how long
+const RATE_LIMIT_FALLBACK_WAIT: chrono::Duration = chrono::Duration::minutes(1);
 
 pub enum PollMessage {
-    Delivery(WebhookDeliveryDetails),
+    /// Details of a delivery, along with the index of the source it came from
+    Delivery { source: usize, details: WebhookDeliveryDetails },
     TimedOut,
+    /// Polling hit an error it couldn't recover from, even after retrying. Nothing follows it.
+    Failed(anyhow::Error),
+    /// The details of this delivery from this source couldn't be fetched, it won't be forwarded
+    Missed { source: usize, delivery_id: u64, error: anyhow::Error },
+}
+
+/// How to poll for deliveries
+#[derive(Clone, Debug)]
+pub struct PollConfig {
+    /// Time between polls right after a delivery
+    pub interval: Duration,
+    /// Time between polls once idle for a while. The same as `interval` for a fixed cadence.
+    pub max_interval: Duration,
+    /// Slow down as the rate limit budget runs low, instead of only when it's nearly gone
+    pub weigh_by_rate_limit: bool,
+    /// Stop polling after this long without deliveries, `None` to keep going forever
+    pub idle_timeout: Option<Duration>,
+    /// Instead of stopping at the idle timeout, keep polling every `KEEP_ALIVE_INTERVAL` until
+    /// something is delivered again
+    pub keep_alive: bool,
+    /// How many delivery details to fetch at once
+    pub fetch_concurrency: usize,
+    /// Forward at most this many of the deliveries made before polling started, the most recent ones
+    pub catch_up_limit: Option<usize>,
+    /// Which attempts of a redelivered event to forward
+    pub dedupe: Dedupe,
+}
+
+/// A webhook to poll for deliveries
+pub struct Source {
+    pub gh: GitHub,
+    pub webhook_id: u32,
+    /// Where to pick up from, or if not given, from now on
+    pub resume_from: Option<Cursor>,
+    /// Events already forwarded from this webhook, kept up to date by whoever forwards them
+    pub seen_guids: Arc<Mutex<SeenGuids>>,
+    /// Deliveries whose details are still to be tried again, kept up to date by the poller so they
+    /// can be listed if the session is cut short
+    pub retrying: Arc<Mutex<Vec<u64>>>,
+}
+
+/// Where polling a source is at
+struct SourceState {
+    gh: GitHub,
+    webhook_id: u32,
+    start_time: DateTime<Utc>,
+    last_id: Option<u64>,
+    last_delivery_time: Option<DateTime<Utc>>,
+    seen_guids: Arc<Mutex<SeenGuids>>,
+    cadence: Cadence,
+    catch_up_limit: Option<usize>,
+    retries: RetryQueue,
+    next_poll: Instant,
+}
+
+impl SourceState {
+    fn new(index: usize, source: Source, started_at: DateTime<Utc>, config: &PollConfig) -> Self {
+        let resume_from = source.resume_from;
+        SourceState {
+            gh: source.gh,
+            webhook_id: source.webhook_id,
+            start_time: resume_from.as_ref().map_or(started_at, |cursor| cursor.timestamp),
+            last_id: resume_from.and_then(|cursor| cursor.last_delivery_id),
+            last_delivery_time: None,
+            seen_guids: source.seen_guids,
+            cadence: Cadence::new(config.interval, config.max_interval),
+            catch_up_limit: config.catch_up_limit,
+            retries: RetryQueue::new(index, source.retrying),
+            next_poll: Instant::now(),
+        }
+    }
 }
 
-pub fn poll(tx: Sender<PollMessage>, gh: &GitHub, webhook: &CreateWebhookResponse) {
-    let start_time: DateTime<Utc> = Utc::now();
-    let mut last_id: Option<u64> = None;
-    let mut last_delivery_time: Option<DateTime<Utc>> = None;
+/// Poll each source for deliveries made after its `resume_from`, or if not given, from now on.
+/// Deliveries made before polling started are caught up on first, oldest first, same as the ones
+/// after. Sources are polled round-robin, each at its own cadence, from a single thread, so they
+/// share one rate limit budget and never poll at the same time.
+pub fn poll(tx: Sender<PollMessage>, sources: Vec<Source>, config: &PollConfig) {
+    // delivered_at only has whole seconds, so without truncating, something delivered in the same
+    // second we started would look older than the start time and be skipped
+    let started_at: DateTime<Utc> = Utc::now().trunc_subsecs(0);
+    let mut sources: Vec<SourceState> = sources.into_iter()
+        .enumerate()
+        .map(|(index, source)| SourceState::new(index, source, started_at, config))
+        .collect();
+    // time spent waiting out the rate limit isn't time without deliveries, so it moves this forward
+    let mut active_since = started_at;
+    let mut dozing = false;
 
     loop {
-        // If we haven't received any deliveries in the last 10 minutes, terminate
-        if should_terminate(last_delivery_time.unwrap_or(start_time)) {
+        // whichever source is due first, on a tie the one that comes first
+        let Some(next) = (0..sources.len()).min_by_key(|&index| sources[index].next_poll) else {
+            return;
+        };
+        thread::sleep(sources[next].next_poll.saturating_duration_since(Instant::now()));
+
+        // If we haven't received any deliveries for a while, terminate, or in keep-alive mode slow way down
+        // catching up on old deliveries when resuming doesn't count as activity
+        let last_activity = sources.iter()
+            .filter_map(|source| source.last_delivery_time)
+            .max()
+            .map_or(active_since, |time| time.max(active_since));
+        let idle = is_idle(last_activity, config.idle_timeout);
+        if idle && !config.keep_alive {
+            give_up(&tx, &mut sources);
             tx.send(PollMessage::TimedOut).unwrap();
             break;
         }
+        if idle && !dozing {
+            log::info!("No deliveries for a while, polling every {} minutes until one shows up", KEEP_ALIVE_INTERVAL.as_secs() / 60);
+        } else if !idle && dozing {
+            log::info!("Deliveries are coming in again, back to polling every {:.1?}", config.interval);
+        }
+        dozing = idle;
 
+        let source = &mut sources[next];
         log::debug!("Polling for webhook deliveries");
-        let deliveries = gh.get_webhook_deliveries(webhook.id);
-        if let Ok(deliveries) = deliveries {
-            log::debug!("Received {} deliveries", deliveries.len());
-
-            for delivery in deliveries.iter().rev() {
-                if let Some(last_delivery_id) = last_id { // If we have a last_id, only get deliveries that are newer
-                    if delivery.id > last_delivery_id {
-                        last_id = Some(delivery.id);
-                        last_delivery_time = Some(delivery.delivered_at);
-                        send_details(&tx, webhook, delivery, gh);
-                    }
-                } else if delivery.delivered_at > start_time { // If we don't have a last_id, only get deliveries that are newer than the start time
-                    last_id = Some(delivery.id);
-                    last_delivery_time = Some(delivery.delivered_at);
-                    send_details(&tx, webhook, delivery, gh);
+        let limit = source.catch_up_limit.take();
+        let mut deliveries = match new_deliveries(&source.gh, source.webhook_id, source.last_id, source.start_time, limit) {
+            Ok(deliveries) => deliveries,
+            Err(e) if rate_limited_until(&e).is_some() => {
+                source.catch_up_limit = limit;
+                // Running out of budget isn't fatal, wait for it to come back and pick up where we left off.
+                // The budget is shared, so every source waits.
+                let reset_at = rate_limited_until(&e).flatten().unwrap_or_else(|| Utc::now() + RATE_LIMIT_FALLBACK_WAIT);
+                log::warn!("Rate limited by GitHub, pausing polling until {}", reset_at.to_rfc3339());
+                let pause = reset_at.signed_duration_since(Utc::now()).max(chrono::Duration::zero());
+                thread::sleep(pause.to_std().unwrap_or_default());
+                active_since = last_activity + pause;
+                continue;
+            }
+            Err(e) => {
+                give_up(&tx, &mut sources);
+                tx.send(PollMessage::Failed(e)).unwrap();
+                break;
+            }
+        };
+        log::debug!("Received {} new deliveries", deliveries.len());
+
+        deliveries.sort_by_key(|d| d.id);
+        if let Some(newest) = deliveries.last() {
+            source.last_id = Some(newest.id);
+            source.last_delivery_time = Some(newest.delivered_at);
+        }
+        let had_deliveries = !deliveries.is_empty();
+        let deliveries = dedupe::filter(deliveries, config.dedupe, &source.seen_guids.lock().unwrap());
+        for (index, e) in send_details(&tx, next, source.webhook_id, &deliveries, &source.gh, config.fetch_concurrency) {
+            source.retries.schedule(&tx, deliveries[index].clone(), 1, e);
+        }
+
+        // another attempt of the same event may have been forwarded in the meantime
+        let (due, attempts): (Vec<_>, Vec<_>) = source.retries.take_due(Instant::now()).into_iter()
+            .filter(|(delivery, _)| {
+                let forwarded = dedupe::already_forwarded(delivery, config.dedupe, &source.seen_guids.lock().unwrap());
+                if forwarded {
+                    source.retries.done(delivery.id);
                 }
+                !forwarded
+            })
+            .unzip();
+        let mut failed: BTreeMap<_, _> = send_details(&tx, next, source.webhook_id, &due, &source.gh, config.fetch_concurrency)
+            .into_iter()
+            .collect();
+        for (index, delivery) in due.into_iter().enumerate() {
+            match failed.remove(&index) {
+                Some(e) => source.retries.schedule(&tx, delivery, attempts[index] + 1, e),
+                None => source.retries.done(delivery.id),
             }
-        } else if let Err(e) = deliveries {
-            log::error!("Error polling for payloads: {:?}", e);
+        }
+
+        let mut interval = source.cadence.next(had_deliveries);
+        if config.weigh_by_rate_limit {
+            interval = weigh_by_budget(interval, &source.gh.rate_limit(), config.max_interval);
+        }
+        if dozing && !had_deliveries {
+            interval = interval.max(KEEP_ALIVE_INTERVAL);
+        }
+        log::debug!("Next poll in {:.1?}", interval);
+        source.next_poll = Instant::now() + interval;
+    }
+}
+
+/// Give up on the deliveries every source was going to retry
+fn give_up(tx: &Sender<PollMessage>, sources: &mut [SourceState]) {
+    for source in sources {
+        source.retries.give_up(tx);
+    }
+}
+
+/// Collect the deliveries we haven't seen yet, newest first. If we have a last_id, only deliveries
+/// that are newer count, otherwise only ones newer than the start time. Deliveries come back newest
+/// first, so paging stops at the first one we've already seen, or once `limit` are collected.
+fn new_deliveries(
+    gh: &GitHub,
+    webhook_id: u32,
+    last_id: Option<u64>,
+    start_time: DateTime<Utc>,
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<WebhookDelivery>> {
+    let mut deliveries = Vec::new();
+    for delivery in gh.get_webhook_deliveries(webhook_id) {
+        if limit.is_some_and(|limit| deliveries.len() >= limit) {
+            break;
+        }
+        let delivery = delivery?;
+        let is_new = match last_id {
+            Some(last_delivery_id) => delivery.id > last_delivery_id,
+            None => delivery.delivered_at >= start_time,
+        };
+        if !is_new {
             break;
         }
+        deliveries.push(delivery);
+    }
+    Ok(deliveries)
+}
+
+/// Polls quickly right after activity and backs off geometrically toward a ceiling while idle
+struct Cadence {
+    base: Duration,
+    ceiling: Duration,
+    current: Duration,
+}
+
+impl Cadence {
+    fn new(base: Duration, ceiling: Duration) -> Self {
+        Cadence { base, ceiling: ceiling.max(base), current: base }
+    }
+
+    /// Time until the next poll, given whether the last one found anything
+    fn next(&mut self, had_deliveries: bool) -> Duration {
+        if had_deliveries {
+            self.current = self.base;
+            return self.base;
+        }
+        let next = self.current.mul_f64(IDLE_BACKOFF_FACTOR).min(self.ceiling);
+        std::mem::replace(&mut self.current, next)
+    }
+}
+
+/// Stretch the interval as the rate limit budget drains, so a long running session leaves room for
+/// other tools sharing the token. Never goes past the ceiling, unless the interval already does.
+fn weigh_by_budget(interval: Duration, rate_limit: &RateLimit, ceiling: Duration) -> Duration {
+    match rate_limit.remaining_fraction() {
+        Some(fraction) if fraction < BUDGET_WEIGHT_THRESHOLD => {
+            let weight = BUDGET_WEIGHT_THRESHOLD / fraction.max(0.01);
+            interval.mul_f64(weight).min(ceiling.max(interval))
+        }
+        _ => interval,
+    }
+}
 
-        thread::sleep(Duration::from_secs(5)); // Sleep for 5 seconds
+/// `Some` if the error is GitHub telling us to slow down, with the time it resets if known
+fn rate_limited_until(e: &anyhow::Error) -> Option<Option<DateTime<Utc>>> {
+    match e.downcast_ref::<GitHubError>() {
+        Some(GitHubError::RateLimited { reset_at, .. }) => Some(*reset_at),
+        _ => None,
     }
 }
 
-fn should_terminate(last_delivery_time: DateTime<Utc>) -> bool {
-    let now = Utc::now();
-    let duration = now.signed_duration_since(last_delivery_time);
-    duration.num_minutes() >= 10
+/// Whether nothing has been delivered for longer than the idle timeout
+fn is_idle(last_delivery_time: DateTime<Utc>, idle_timeout: Option<Duration>) -> bool {
+    let Some(idle_timeout) = idle_timeout else {
+        return false;
+    };
+    let idle = Utc::now().signed_duration_since(last_delivery_time).to_std().unwrap_or_default();
+    idle >= idle_timeout
 }
 
+/// Deliveries whose details couldn't be fetched, waiting to be tried again
+struct RetryQueue {
+    /// Index of the source the deliveries came from
+    source: usize,
+    /// The delivery, how many attempts were made so far, when to try again, and why the last one failed
+    failed: Vec<(WebhookDelivery, u32, Instant, anyhow::Error)>,
+    /// Ids of the deliveries not given up on or fetched yet, including the ones being tried right now
+    retrying: Arc<Mutex<Vec<u64>>>,
+}
+
+impl RetryQueue {
+    fn new(source: usize, retrying: Arc<Mutex<Vec<u64>>>) -> Self {
+        RetryQueue { source, failed: Vec::new(), retrying }
+    }
+
+    /// Queue a delivery to be tried again after `attempts` failed, or give up on it if that was the last one
+    fn schedule(&mut self, tx: &Sender<PollMessage>, delivery: WebhookDelivery, attempts: u32, e: anyhow::Error) {
+        if attempts >= DETAIL_FETCH_ATTEMPTS {
+            self.done(delivery.id);
+            let error = e.context(format!("{} attempts failed", attempts));
+            tx.send(PollMessage::Missed { source: self.source, delivery_id: delivery.id, error }).unwrap();
+            return;
+        }
+
+        let delay = DETAIL_RETRY_DELAY * 2u32.pow(attempts - 1);
+        log::warn!("Error getting delivery details for {}, trying again in {:?}: {:#}", delivery.id, delay, e);
+        let mut retrying = self.retrying.lock().unwrap();
+        if !retrying.contains(&delivery.id) {
+            retrying.push(delivery.id);
+        }
+        self.failed.push((delivery, attempts, Instant::now() + delay, e));
+    }
+
+    /// Stop tracking a delivery taken off the queue, its details were fetched or it's not needed anymore
+    fn done(&self, delivery_id: u64) {
+        self.retrying.lock().unwrap().retain(|&id| id != delivery_id);
+    }
+
+    /// Take the deliveries that are due to be tried again, along with how many attempts were made so far
+    fn take_due(&mut self, now: Instant) -> Vec<(WebhookDelivery, u32)> {
+        let (due, waiting) = std::mem::take(&mut self.failed).into_iter()
+            .partition(|(_, _, retry_at, _)| *retry_at <= now);
+        self.failed = waiting;
+        due.into_iter().map(|(delivery, attempts, _, _)| (delivery, attempts)).collect()
+    }
+
+    /// Report everything still waiting as missed, polling is about to stop
+    fn give_up(&mut self, tx: &Sender<PollMessage>) {
+        for (delivery, attempts, _, e) in std::mem::take(&mut self.failed) {
+            self.done(delivery.id);
+            let error = e.context(format!("Polling stopped after {} failed attempt(s)", attempts));
+            tx.send(PollMessage::Missed { source: self.source, delivery_id: delivery.id, error }).unwrap();
+        }
+    }
+}
+
+/// Fetch the details of each delivery, up to `concurrency` at a time, and send them on in the order
+/// the deliveries were given. A delivery is sent as soon as it and everything before it is fetched,
+/// so one slow request only holds back the deliveries after it. Returns the ones that failed by index.
 fn send_details(
     tx: &Sender<PollMessage>,
-    webhook: &CreateWebhookResponse,
-    delivery: &WebhookDelivery,
+    source: usize,
+    webhook_id: u32,
+    deliveries: &[WebhookDelivery],
     gh: &GitHub,
-) {
-    log::debug!("Getting details for delivery: {:?}", delivery.id);
-    let details_resp = gh.get_webhook_delivery_details(webhook.id, delivery.id);
-
-    if let Ok(details) = details_resp {
-        tx.send(PollMessage::Delivery(details)).unwrap();
-    } else {
-        log::error!("Error getting delivery details: {:?}", delivery.id);
+    concurrency: usize,
+) -> Vec<(usize, anyhow::Error)> {
+    let mut failed = Vec::new();
+    if deliveries.is_empty() {
+        return failed;
     }
+    let next_to_fetch = AtomicUsize::new(0);
+    let (results_tx, results_rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, deliveries.len().max(1)) {
+            let results_tx = results_tx.clone();
+            let next_to_fetch = &next_to_fetch;
+            scope.spawn(move || loop {
+                let index = next_to_fetch.fetch_add(1, Ordering::Relaxed);
+                let Some(delivery) = deliveries.get(index) else { break };
+                log::debug!("Getting details for delivery: {:?}", delivery.id);
+                let details = gh.get_webhook_delivery_details(webhook_id, delivery.id);
+                if results_tx.send((index, details)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(results_tx);
+
+        // results arrive in whatever order the requests finish, hold on to them until it's their turn
+        let mut pending = BTreeMap::new();
+        let mut next_to_send = 0;
+        for (index, details) in results_rx {
+            pending.insert(index, details);
+            while let Some(details) = pending.remove(&next_to_send) {
+                match details {
+                    Ok(details) => tx.send(PollMessage::Delivery { source, details }).unwrap(),
+                    Err(e) => failed.push((next_to_send, e)),
+                }
+                next_to_send += 1;
+            }
+        }
+    });
+    failed
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use chrono::Utc;
 
+    use crate::ratelimit::RateLimit;
+
+    use super::{weigh_by_budget, Cadence, PollMessage, RetryQueue, DEFAULT_IDLE_TIMEOUT, DETAIL_FETCH_ATTEMPTS};
+
+    fn delivery(id: u64) -> crate::gh::WebhookDelivery {
+        crate::gh::WebhookDelivery {
+            id,
+            guid: None,
+            delivered_at: Utc::now(),
+            redelivery: false,
+            event: "issues".to_string(),
+            action: None,
+        }
+    }
+
+    #[test]
+    fn test_retry_queue_waits_before_retrying() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut retries = RetryQueue::new(0, Default::default());
+        retries.schedule(&tx, delivery(1), 1, anyhow::anyhow!("boom"));
+
+        assert!(retries.take_due(std::time::Instant::now()).is_empty());
+        let due = retries.take_due(std::time::Instant::now() + Duration::from_secs(60));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].1, 1);
+        assert!(retries.take_due(std::time::Instant::now() + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_retry_queue_gives_up_after_max_attempts() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut retries = RetryQueue::new(0, Default::default());
+        retries.schedule(&tx, delivery(1), DETAIL_FETCH_ATTEMPTS, anyhow::anyhow!("boom"));
+        retries.schedule(&tx, delivery(2), 1, anyhow::anyhow!("boom"));
+        retries.give_up(&tx);
+
+        let missed: Vec<(u64, String)> = rx.try_iter()
+            .filter_map(|message| match message {
+                PollMessage::Missed { delivery_id, error, .. } => Some((delivery_id, format!("{:#}", error))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(missed, vec![
+            (1, format!("{} attempts failed: boom", DETAIL_FETCH_ATTEMPTS)),
+            (2, "Polling stopped after 1 failed attempt(s): boom".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_retry_queue_tracks_deliveries_until_done() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let retrying = std::sync::Arc::default();
+        let mut retries = RetryQueue::new(0, std::sync::Arc::clone(&retrying));
+        retries.schedule(&tx, delivery(1), 1, anyhow::anyhow!("boom"));
+        retries.schedule(&tx, delivery(2), 1, anyhow::anyhow!("boom"));
+
+        // still listed while being tried again
+        retries.take_due(std::time::Instant::now() + Duration::from_secs(60));
+        assert_eq!(*retrying.lock().unwrap(), vec![1, 2]);
+        retries.schedule(&tx, delivery(1), 2, anyhow::anyhow!("boom"));
+        retries.done(2);
+        assert_eq!(*retrying.lock().unwrap(), vec![1]);
+        retries.give_up(&tx);
+        assert!(retrying.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cadence_backs_off_while_idle() {
+        let mut cadence = Cadence::new(Duration::from_secs(2), Duration::from_secs(5));
+        assert_eq!(cadence.next(false), Duration::from_secs(2));
+        assert_eq!(cadence.next(false), Duration::from_secs(3));
+        assert_eq!(cadence.next(false), Duration::from_millis(4500));
+        assert_eq!(cadence.next(false), Duration::from_secs(5));
+        assert_eq!(cadence.next(false), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_cadence_resets_on_activity() {
+        let mut cadence = Cadence::new(Duration::from_secs(2), Duration::from_secs(60));
+        cadence.next(false);
+        cadence.next(false);
+        assert_eq!(cadence.next(true), Duration::from_secs(2));
+        assert_eq!(cadence.next(false), Duration::from_secs(2));
+        assert_eq!(cadence.next(false), Duration::from_secs(3));
+    }
+
     #[test]
-    fn test_should_terminate() {
+    fn test_weigh_by_budget() {
+        let interval = Duration::from_secs(5);
+        let ceiling = Duration::from_secs(60);
+        let budget = |remaining| RateLimit { limit: Some(5000), remaining: Some(remaining), reset_at: None };
+
+        assert_eq!(weigh_by_budget(interval, &budget(4000), ceiling), interval);
+        assert_eq!(weigh_by_budget(interval, &budget(1250), ceiling), Duration::from_secs(10));
+        assert_eq!(weigh_by_budget(interval, &budget(10), ceiling), ceiling);
+        assert_eq!(weigh_by_budget(interval, &RateLimit::default(), ceiling), interval);
+    }
+
+    #[test]
+    fn test_is_idle() {
         let now = Utc::now();
         let time = now - chrono::Duration::minutes(11);
-        assert!(super::should_terminate(time));
+        assert!(super::is_idle(time, Some(DEFAULT_IDLE_TIMEOUT)));
     }
 
     #[test]
-    fn test_should_not_terminate() {
+    fn test_is_not_idle() {
         let now = Utc::now();
         let time = now - chrono::Duration::minutes(9);
-        assert!(!super::should_terminate(time));
+        assert!(!super::is_idle(time, Some(DEFAULT_IDLE_TIMEOUT)));
+    }
+
+    #[test]
+    fn test_never_idle_without_idle_timeout() {
+        let time = Utc::now() - chrono::Duration::days(7);
+        assert!(!super::is_idle(time, None));
     }
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::HeaderMap;
+
+/// Once the remaining budget drops below this fraction of the limit, requests are spread out
+/// evenly over the time left until the reset instead of being sent as fast as possible.
+const LOW_BUDGET_RATIO: f64 = 0.1;
+
+/// Never pace a single request by more than this, the next response will refresh our view
+const MAX_PACING_DELAY: Duration = Duration::from_secs(60);
+
+/// Last known state of the primary rate limit, as reported by the `X-RateLimit-*` headers
+#[derive(Debug, Default, Clone)]
+pub struct RateLimit {
+    pub limit: Option<u32>,
+    pub remaining: Option<u32>,
+    pub reset_at: Option<DateTime<Utc>>,
+}
+
+impl RateLimit {
+    /// Update from the headers of a response. Headers that are missing (e.g. on GHES instances
+    /// with rate limiting disabled) leave the previous values alone.
+    pub fn update(&mut self, headers: &HeaderMap) {
+        if let Some(limit) = header_u64(headers, "x-ratelimit-limit") {
+            self.limit = Some(limit as u32);
+        }
+        if let Some(remaining) = header_u64(headers, "x-ratelimit-remaining") {
+            self.remaining = Some(remaining as u32);
+        }
+        if let Some(reset) = header_u64(headers, "x-ratelimit-reset") {
+            self.reset_at = Utc.timestamp_opt(reset as i64, 0).single();
+        }
+    }
+
+    /// Whether the budget is used up and hasn't reset yet
+    pub fn is_exhausted(&self, now: DateTime<Utc>) -> bool {
+        self.remaining == Some(0) && self.reset_at.is_some_and(|reset| reset > now)
+    }
+
+    /// Share of the budget that's left, between 0 and 1, if known
+    pub fn remaining_fraction(&self) -> Option<f64> {
+        match (self.remaining, self.limit) {
+            (Some(remaining), Some(limit)) if limit > 0 => Some((remaining as f64 / limit as f64).min(1.0)),
+            _ => None,
+        }
+    }
+
+    /// How long to wait before the next request so the remaining budget lasts until the reset
+    pub fn pacing_delay(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let (Some(limit), Some(remaining), Some(reset_at)) = (self.limit, self.remaining, self.reset_at) else {
+            return None;
+        };
+        if remaining == 0 || (remaining as f64) >= (limit as f64) * LOW_BUDGET_RATIO {
+            return None;
+        }
+
+        let until_reset = reset_at.signed_duration_since(now).to_std().ok()?;
+        Some((until_reset / remaining).min(MAX_PACING_DELAY))
+    }
+}
+
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.remaining, self.limit) {
+            (Some(remaining), Some(limit)) => write!(f, "{}/{} requests remaining", remaining, limit)?,
+            (Some(remaining), None) => write!(f, "{} requests remaining", remaining)?,
+            _ => write!(f, "unknown budget")?,
+        }
+        if let Some(reset_at) = self.reset_at {
+            write!(f, ", resets at {}", reset_at.to_rfc3339())?;
+        }
+        Ok(())
+    }
+}
+
+/// When a rate limited request can be retried. `Retry-After` (secondary limits) takes precedence,
+/// then `X-RateLimit-Reset` if the primary limit is used up.
+pub fn retry_at(headers: &HeaderMap, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Some(seconds) = header_u64(headers, "retry-after") {
+        return Some(now + chrono::Duration::seconds(seconds as i64));
+    }
+    if header_u64(headers, "x-ratelimit-remaining") == Some(0) {
+        if let Some(reset) = header_u64(headers, "x-ratelimit-reset") {
+            return Utc.timestamp_opt(reset as i64, 0).single();
+        }
+    }
+    None
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use reqwest::header::HeaderMap;
+
+    use super::{retry_at, RateLimit};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (k, v) in pairs {
+            headers.insert(*k, v.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_update() {
+        let mut rate_limit = RateLimit::default();
+        rate_limit.update(&headers(&[
+            ("x-ratelimit-limit", "5000"),
+            ("x-ratelimit-remaining", "4999"),
+            ("x-ratelimit-reset", "1700000000"),
+        ]));
+
+        assert_eq!(rate_limit.limit, Some(5000));
+        assert_eq!(rate_limit.remaining, Some(4999));
+        assert_eq!(rate_limit.reset_at, Utc.timestamp_opt(1700000000, 0).single());
+
+        // missing headers keep what we already knew
+        rate_limit.update(&HeaderMap::new());
+        assert_eq!(rate_limit.remaining, Some(4999));
+    }
+
+    #[test]
+    fn test_pacing_delay() {
+        let now = Utc::now();
+        let mut rate_limit = RateLimit {
+            limit: Some(5000),
+            remaining: Some(4000),
+            reset_at: Some(now + chrono::Duration::seconds(100)),
+        };
+        assert!(rate_limit.pacing_delay(now).is_none());
+
+        rate_limit.remaining = Some(10);
+        assert_eq!(rate_limit.pacing_delay(now), Some(Duration::from_secs(10)));
+
+        rate_limit.remaining = Some(1);
+        assert_eq!(rate_limit.pacing_delay(now), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_is_exhausted() {
+        let now = Utc::now();
+        let mut rate_limit = RateLimit {
+            limit: Some(5000),
+            remaining: Some(0),
+            reset_at: Some(now + chrono::Duration::seconds(100)),
+        };
+        assert!(rate_limit.is_exhausted(now));
+
+        rate_limit.reset_at = Some(now - chrono::Duration::seconds(1));
+        assert!(!rate_limit.is_exhausted(now));
+    }
+
+    #[test]
+    fn test_retry_at() {
+        let now = Utc::now();
+        assert_eq!(retry_at(&headers(&[("retry-after", "30")]), now), Some(now + chrono::Duration::seconds(30)));
+        assert_eq!(
+            retry_at(&headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "1700000000")]), now),
+            Utc.timestamp_opt(1700000000, 0).single()
+        );
+        assert!(retry_at(&headers(&[("x-ratelimit-remaining", "10"), ("x-ratelimit-reset", "1700000000")]), now).is_none());
+    }
+}
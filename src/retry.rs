@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+/// How to retry requests that failed for reasons that are likely to go away on their own
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Delay before the first retry
+    pub initial_interval: Duration,
+    /// Upper bound for the delay between two attempts
+    pub max_interval: Duration,
+    /// Growth of the delay from one retry to the next
+    pub multiplier: f64,
+    /// Give up once this much time has passed since the first attempt
+    pub max_elapsed: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            initial_interval: Duration::from_millis(500),
+            max_interval: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_elapsed: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Start tracking retries for one request
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            policy: self.clone(),
+            started: Instant::now(),
+            interval: self.initial_interval,
+        }
+    }
+}
+
+/// Retry state for a single request
+pub struct Backoff {
+    policy: RetryPolicy,
+    started: Instant,
+    interval: Duration,
+}
+
+impl Backoff {
+    /// How long to wait before retrying, or `None` if retrying would exceed the max elapsed time.
+    /// Delays are randomized by ±50% so clients that failed together don't retry together.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        let delay = self.interval.mul_f64(0.5 + fastrand::f64());
+        if self.started.elapsed() + delay > self.policy.max_elapsed {
+            return None;
+        }
+
+        self.interval = self.interval.mul_f64(self.policy.multiplier).min(self.policy.max_interval);
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn test_backoff_grows_until_max_interval() {
+        let policy = RetryPolicy {
+            initial_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(4),
+            multiplier: 2.0,
+            max_elapsed: Duration::from_secs(3600),
+        };
+        let mut backoff = policy.backoff();
+
+        let bounds = [(0.5, 1.5), (1.0, 3.0), (2.0, 6.0), (2.0, 6.0)];
+        for (min, max) in bounds {
+            let delay = backoff.next_delay().unwrap().as_secs_f64();
+            assert!(delay >= min && delay <= max, "{} not in {}..{}", delay, min, max);
+        }
+    }
+
+    #[test]
+    fn test_backoff_gives_up() {
+        let policy = RetryPolicy {
+            initial_interval: Duration::from_secs(10),
+            max_elapsed: Duration::from_secs(1),
+            ..Default::default()
+        };
+        assert!(policy.backoff().next_delay().is_none());
+    }
+}
@@ -0,0 +1,163 @@
+use std::{io::Write, path::{Path, PathBuf}};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::dedupe::SeenGuids;
+
+/// Where a forwarding session left off, so the next one can pick up from there
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub webhook_id: u32,
+    /// Newest delivery that was forwarded, if any
+    pub last_delivery_id: Option<u64>,
+    /// When that delivery was made, or when the session started if nothing was forwarded yet
+    pub timestamp: DateTime<Utc>,
+    /// Events forwarded recently, so `--dedupe` still recognizes their redeliveries after a restart
+    #[serde(default)]
+    pub seen_guids: SeenGuids,
+}
+
+/// Cursors kept between sessions, one JSON file per location so sessions for different locations
+/// never write to the same file
+pub struct StateDir {
+    dir: PathBuf,
+}
+
+impl StateDir {
+    pub fn new(dir: PathBuf) -> Self {
+        StateDir { dir }
+    }
+
+    /// The state directory in the `gh` config directory
+    pub fn in_config_dir() -> anyhow::Result<Self> {
+        let dir = gh_config_dir().context("Could not find the gh config directory, set GH_CONFIG_DIR")?;
+        Ok(StateDir::new(dir.join(env!("CARGO_PKG_NAME")).join("cursors")))
+    }
+
+    /// File the cursor for a location is kept in. Keys are URLs, see `GitHub::location`.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", file_name(key)))
+    }
+
+    pub fn cursor(&self, key: &str) -> anyhow::Result<Option<Cursor>> {
+        let path = self.path(key);
+        match std::fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .with_context(|| format!("{} is not a valid cursor", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Write to a temporary file first, so a session killed halfway through never leaves a truncated file
+    pub fn save_cursor(&self, key: &str, cursor: &Cursor) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        write_synced(&tmp, &serde_json::to_vec_pretty(cursor)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Write a file and wait for it to hit the disk, so renaming it never publishes a partial write
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// A file name for a key that's distinct for every key. Anything but letters, digits, `-` and `.`
+/// is written as `_` and its hex code, `_` included.
+fn file_name(key: &str) -> String {
+    key.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' => (b as char).to_string(),
+            b => format!("_{:02X}", b),
+        })
+        .collect()
+}
+
+/// Where `gh` keeps its configuration, looked up the same way `gh` does
+fn gh_config_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+
+    if let Some(dir) = var("GH_CONFIG_DIR") {
+        return Some(dir);
+    }
+    if let Some(dir) = var("XDG_CONFIG_HOME") {
+        return Some(dir.join("gh"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = var("AppData") {
+            return Some(dir.join("GitHub CLI"));
+        }
+    }
+    var("HOME").map(|home| home.join(".config").join("gh"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{file_name, Cursor, StateDir};
+
+    fn state_dir(name: &str) -> StateDir {
+        let dir = std::env::temp_dir().join(format!("{}-{}-{}", env!("CARGO_PKG_NAME"), name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        StateDir::new(dir)
+    }
+
+    #[test]
+    fn test_missing_cursor() {
+        let state = state_dir("missing");
+        assert_eq!(state.cursor("https://api.github.com/repos/org/repo/hooks").unwrap(), None);
+    }
+
+    #[test]
+    fn test_save_cursor() {
+        let state = state_dir("save");
+        let cursor = Cursor {
+            webhook_id: 1,
+            last_delivery_id: Some(42),
+            timestamp: Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap(),
+            seen_guids: vec!["72d3162e-cc78-11e3-81ab-4c9367dc0958".to_string()].into(),
+        };
+
+        state.save_cursor("https://api.github.com/repos/org/repo/hooks", &cursor).unwrap();
+        state.save_cursor("https://api.github.com/orgs/org/hooks", &Cursor { webhook_id: 2, ..cursor.clone() }).unwrap();
+
+        assert_eq!(state.cursor("https://api.github.com/repos/org/repo/hooks").unwrap(), Some(cursor));
+        assert_eq!(state.cursor("https://api.github.com/orgs/org/hooks").unwrap().unwrap().webhook_id, 2);
+        std::fs::remove_dir_all(&state.dir).unwrap();
+    }
+
+    #[test]
+    fn test_broken_cursor_only_affects_its_location() {
+        let state = state_dir("broken");
+        std::fs::create_dir_all(&state.dir).unwrap();
+        std::fs::write(state.path("https://api.github.com/repos/org/a/hooks"), "{").unwrap();
+        let cursor = Cursor {
+            webhook_id: 1,
+            last_delivery_id: None,
+            timestamp: Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap(),
+            seen_guids: Default::default(),
+        };
+
+        assert!(state.cursor("https://api.github.com/repos/org/a/hooks").is_err());
+        state.save_cursor("https://api.github.com/repos/org/b/hooks", &cursor).unwrap();
+        assert_eq!(state.cursor("https://api.github.com/repos/org/b/hooks").unwrap(), Some(cursor.clone()));
+        state.save_cursor("https://api.github.com/repos/org/a/hooks", &cursor).unwrap();
+        assert_eq!(state.cursor("https://api.github.com/repos/org/a/hooks").unwrap(), Some(cursor));
+        std::fs::remove_dir_all(&state.dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_names_are_distinct() {
+        assert_eq!(file_name("https://api.github.com/repos/org/repo/hooks"), "https_3A_2F_2Fapi.github.com_2Frepos_2Forg_2Frepo_2Fhooks");
+        assert_ne!(file_name("repos/org_a/b"), file_name("repos/org/a_b"));
+    }
+}
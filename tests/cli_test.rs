@@ -1,4 +1,4 @@
-use std::{env, process::{Child, Command, Stdio}};
+use std::{env, path::{Path, PathBuf}, process::{Child, Command, Stdio}};
 
 use httpmock::MockServer;
 use serde_json::json;
@@ -25,80 +25,1493 @@ fn test_mock_gh() {
 
 #[test]
 fn test_run_with_org() {
-    let result = run_cli_forward(vec!["--org", "test"]).unwrap().wait_with_output().unwrap();
+    let gh_server = MockGhServer::with_hooks_path("/orgs/test/hooks");
+    gh_server.add_all_mocks();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--org", "test"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
+}
+
+#[test]
+fn test_run_with_enterprise() {
+    let gh_server = MockGhServer::with_hooks_path("/admin/hooks");
+    gh_server.add_all_mocks();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--enterprise", "--events", "user,organization"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
+}
+
+#[test]
+fn test_enterprise_creates_web_hook_and_explains_missing_deliveries() {
+    let gh_server = MockGhServer::with_hooks_path("/admin/hooks");
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/admin/hooks")
+            .json_body_includes(json!({"name": "web", "config": {"url": "https://gh-ghes-webhook.invalid/"}}).to_string());
+        then.status(201)
+            .body(json!({"id": 1, "config": {}, "name": "web", "events": ["user"], "active": true}).to_string());
+    });
+    let delete_mock = gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/admin/hooks/1/deliveries");
+        then.status(404)
+            .body(json!({"message": "Not Found"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--enterprise", "--events", "user"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("doesn't list the deliveries of global webhooks"));
+    create_mock.assert();
+    delete_mock.assert();
+}
+
+#[test]
+fn test_run_with_app() {
+    let gh_server = MockGhServer::new();
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST);
+        then.status(500);
+    });
+    let deliveries_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/app/hook/deliveries")
+            .header_prefix("authorization", "Bearer ey");
+        then.status(200)
+            .body(json!([delivery(1)]).to_string());
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/app/hook/deliveries/1");
+        then.status(200)
+            .body(delivery_details(1).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--app-id", "1", "--private-key", "tests/fixtures/app-private-key.pem"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
+    deliveries_mock.assert();
+    create_mock.assert_calls(0);
+}
+
+#[test]
+fn test_run_with_app_requires_private_key() {
+    let result = run_cli_forward(vec!["--app-id", "1"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--private-key"));
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_attach_to_webhook() {
+    let gh_server = MockGhServer::new();
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST);
+        then.status(500);
+    });
+    let delete_mock = gh_server.add_delete_webhook();
+    gh_server.add_get_webhook();
+    gh_server.add_get_webhook_deliveries();
+    gh_server.add_get_webhook_delivery_details();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--webhook-id", "1"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
+    ).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Attached to webhook 1 (web), events: issues, pull_request"));
+    assert!(stdout.contains("Forwarding event: 1"));
+    create_mock.assert_calls(0);
+    delete_mock.assert_calls(0);
+}
+
+#[test]
+fn test_replay_since_with_limit() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_get_webhook();
+    let ago = |minutes| (chrono::Utc::now() - chrono::Duration::minutes(minutes)).to_rfc3339();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([
+                {"id": 4, "delivered_at": ago(5), "event": "issues"},
+                {"id": 3, "delivered_at": ago(10), "event": "issues"},
+                {"id": 2, "delivered_at": ago(20), "event": "issues"},
+                {"id": 1, "delivered_at": ago(120), "event": "issues"},
+            ]).to_string());
+    });
+    for id in 2..=4 {
+        gh_server.add_get_webhook_delivery_details_with_id(id);
+    }
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--webhook-id", "1", "--since", "1h", "--limit", "2"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Forwarding event: 1"));
+    assert!(!stdout.contains("Forwarding event: 2"));
+    let third = stdout.find("Forwarding event: 3").expect("delivery 3 was not replayed");
+    let fourth = stdout.find("Forwarding event: 4").expect("delivery 4 was not replayed");
+    assert!(third < fourth, "deliveries were not replayed oldest first");
+}
+
+#[test]
+fn test_limit_requires_since_or_resume() {
+    let result = run_cli_forward(vec!["--repo", "org/repo", "--limit", "2"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--since <SINCE>|--resume"));
+}
+
+#[test]
+fn test_dedupe_first_attempt() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([
+                {"id": 3, "guid": "guid-a", "redelivery": true, "delivered_at": "2099-08-01T00:00:02Z", "event": "issues"},
+                {"id": 2, "guid": "guid-b", "redelivery": false, "delivered_at": "2099-08-01T00:00:01Z", "event": "issues"},
+                {"id": 1, "guid": "guid-a", "redelivery": false, "delivered_at": "2099-08-01T00:00:00Z", "event": "issues"},
+            ]).to_string());
+    });
+    gh_server.add_get_webhook_delivery_details_with_id(1);
+    gh_server.add_get_webhook_delivery_details_with_id(2);
+    let redelivery_details = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/3");
+        then.status(200)
+            .body(delivery_details(3).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--dedupe", "first"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Forwarding event: 1"));
+    assert!(stdout.contains("Forwarding event: 2"));
+    assert!(!stdout.contains("Forwarding event: 3"));
+    redelivery_details.assert_calls(0);
+}
+
+#[test]
+fn test_dedupe_first_forwards_redelivery_of_missed_event() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    let original = json!({"id": 1, "guid": "guid-a", "redelivery": false, "delivered_at": "2099-08-01T00:00:00Z", "event": "issues"});
+    let mut before_redelivery = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([original]).to_string());
+    });
+    // the original attempt never makes it
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/1");
+        then.status(500);
+    });
+    gh_server.add_get_webhook_delivery_details_with_id(2);
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--dedupe", "first", "--interval", "2s"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    before_redelivery.delete();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([
+                {"id": 2, "guid": "guid-a", "redelivery": true, "delivered_at": "2099-08-01T00:00:05Z", "event": "issues"},
+                original,
+            ]).to_string());
+    });
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Forwarding event: 1"));
+    assert!(stdout.contains("Forwarding event: 2"));
+}
+
+#[test]
+fn test_attach_to_missing_webhook() {
+    let gh_server = MockGhServer::new();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--webhook-id", "2"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Failed to find webhook 2: Not found"));
+}
+
+#[test]
+fn test_run_without_location() {
+    let result = run_cli_forward(vec![]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("the following required arguments were not provided"));
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_ctrl_c() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    // sleep for a second to allow the CLI to start
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    // send a SIGINT to the child process (doesn't work that way on windows)
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
+    ).unwrap();
+
+    let result = child.wait_with_output().unwrap();
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Deleting CLI webhook"));
+}
+
+#[test]
+fn test_forward_to_stdout() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--events", "issues,pull_request"]).unwrap();
+
+    // sleep for a second to allow the CLI to grab webhook deliveries
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Content-Type: application/json"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("{\"issue\":{\"body\":\"Test Body\",\"title\":\"Test Issue\"}}"));
+}
+
+#[test]
+fn test_forward_with_api_url() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    let api_url = gh_server.server.url("/");
+
+    let mut child = run_cli_forward(vec!["--github-host", "github.example.com", "--api-url", api_url.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
+}
+
+#[test]
+fn test_forward_through_proxy() {
+    // the mock server stands in for the proxy, so the API host itself doesn't need to exist
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    let proxy = gh_server.server.url("");
+
+    let mut child = run_cli_forward(vec!["--github-host", "github.invalid", "--api-url", "http://github.invalid", "--proxy", proxy.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
+}
+
+#[test]
+fn test_forward_with_missing_ca_cert() {
+    let result = run_cli_forward(vec!["--ca-cert", "tests/fixtures/missing.pem", "--repo", "org/repo"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Failed to read CA certificates from tests/fixtures/missing.pem"));
+}
+
+#[test]
+fn test_foward_to_local_server() {
+    // mock gh server
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    // mock local server
+    let mock_reciever = MockServer::start();
+    let mock_reciever_endpoint= create_mock_reciever_endpoint(&mock_reciever);
+    let url = format!("localhost:{}/test", mock_reciever.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--url", url.as_str()]).unwrap();
+
+    // sleep for a second to allow the CLI to grab webhook deliveries. should forward once
+    std::thread::sleep(std::time::Duration::from_secs(7));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    mock_reciever_endpoint.assert();
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_forward_from_several_repos() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    let other_deleted = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::DELETE)
+            .path("/repos/org/other/hooks/1");
+        then.status(204);
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/other/hooks");
+        then.status(200)
+            .body(json!({"id": 1, "config": {}, "name": "cli", "events": ["issues"], "active": true}).to_string());
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/other/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([delivery(2)]).to_string());
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/other/hooks/1/deliveries/2");
+        then.status(200)
+            .body(delivery_details(2).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mock_reciever = MockServer::start();
+    let from_repo = mock_reciever.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/test")
+            .header("X-GHES-Webhook-Repo", "org/repo");
+        then.status(200);
+    });
+    let from_other = mock_reciever.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/test")
+            .header("X-GHES-Webhook-Repo", "org/other");
+        then.status(200);
+    });
+    let url = format!("localhost:{}/test", mock_reciever.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo,org/other", "--url", url.as_str()]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
+    ).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Forwarding event: 1 from org/repo"));
+    assert!(stdout.contains("Forwarding event: 2 from org/other"));
+    from_repo.assert();
+    from_other.assert();
+    other_deleted.assert();
+    assert_eq!(stdout.matches("Deleting CLI webhook").count(), 2);
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_ctrl_c_while_setting_up_several_repos() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    let deleted = gh_server.add_delete_webhook();
+    // checking the second repository for stale webhooks takes a while, the first one's is already created
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/other/hooks");
+        then.status(200)
+            .delay(std::time::Duration::from_secs(5))
+            .body("[]");
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo,org/other"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
+    ).unwrap();
+
+    let result = child.wait_with_output().unwrap();
+    assert!(result.status.success());
+    deleted.assert();
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_ctrl_c_reports_deliveries_waiting_for_retry() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_deliveries();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/1");
+        then.status(500);
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    // the retry isn't due for a few more seconds
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
+    ).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Permanently missed 1 delivery(s): 1"));
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_ctrl_c_while_forwarding() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_get_webhook_deliveries();
+    gh_server.add_get_webhook_delivery_details();
+    let deleted = gh_server.add_delete_webhook();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    // a local handler that doesn't answer while the test runs
+    let mock_reciever = MockServer::start();
+    mock_reciever.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/test");
+        then.status(200)
+            .delay(std::time::Duration::from_secs(60));
+    });
+    let url = format!("localhost:{}/test", mock_reciever.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--url", url.as_str()]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
+    ).unwrap();
+
+    // the handler is still busy, shutting down mustn't wait for it
+    let signalled_at = std::time::Instant::now();
+    let result = child.wait_with_output().unwrap();
+    assert!(signalled_at.elapsed() < std::time::Duration::from_secs(10));
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Forwarding event: 1"));
+    assert!(result.status.success());
+    deleted.assert();
+}
+
+#[test]
+fn test_webhook_id_needs_a_single_repo() {
+    let result = run_cli_forward(vec!["--repo", "org/repo", "--repo", "org/other", "--webhook-id", "1"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--webhook-id can only be used with a single --repo"));
+}
+
+#[test]
+fn test_create_webhook_not_found() {
+    let gh_server = MockGhServer::new();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks");
+        then.status(404)
+            .body(json!({
+                "message": "Not Found",
+                "documentation_url": "https://docs.github.com/rest/repos/webhooks#create-a-repository-webhook"
+            }).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap().wait_with_output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(!result.status.success());
+    assert!(stderr.contains("Failed to create CLI webhook: Not found - HTTP 404: Not Found"));
+    assert!(stderr.contains("Check the repository or organization name"));
+}
+
+#[test]
+fn test_unknown_event_is_rejected() {
+    let gh_server = MockGhServer::new();
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks");
+        then.status(422);
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--events", "issues,pull_requests"])
+        .unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Unknown event(s): `pull_requests` (did you mean `pull_request`?)"));
+    create_mock.assert_calls(0);
+}
+
+#[test]
+fn test_enterprise_events_default_to_user_and_organization() {
+    let gh_server = MockGhServer::with_hooks_path("/admin/hooks");
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/admin/hooks")
+            .json_body_includes(json!({"events": ["user", "organization"]}).to_string());
+        then.status(201)
+            .body(json!({"id": 1, "config": {}, "name": "web", "events": ["user", "organization"], "active": true}).to_string());
+    });
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_deliveries();
+    gh_server.add_get_webhook_delivery_details();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--enterprise"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    create_mock.assert();
+}
+
+#[test]
+fn test_enterprise_rejects_repository_events() {
+    let result = run_cli_forward(vec!["--enterprise", "--events", "push"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Global webhooks only take `user` and `organization` events, not `push`"));
+}
+
+#[test]
+fn test_events_default_to_push() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_deliveries();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/");
+        then.status(200)
+            .header("X-OAuth-Scopes", "repo, read:org")
+            .body("{}");
+    });
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks")
+            .json_body_includes(json!({"events": ["push"]}).to_string());
+        then.status(200)
+            .body(json!({"id": 1, "name": "cli", "events": ["push"], "active": true}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    create_mock.assert();
+}
+
+#[test]
+fn test_missing_scope_is_reported_before_creating() {
+    let gh_server = MockGhServer::new();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/");
+        then.status(200)
+            .header("X-OAuth-Scopes", "gist, read:org")
+            .body("{}");
+    });
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks");
+        then.status(403);
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Token has none of the required scopes (admin:repo_hook or repo), only gist, read:org"));
+    assert!(stderr.contains("gh auth refresh"));
+    create_mock.assert_calls(0);
+}
+
+#[test]
+fn test_polling_error_deletes_webhook() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    let delete_mock = gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(401)
+            .body(json!({"message": "Bad credentials"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Authentication failed - HTTP 401: Bad credentials"));
+    delete_mock.assert();
+}
+
+#[test]
+fn test_polling_retries_unavailable_github() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    let delete_mock = gh_server.add_delete_webhook();
+    let deliveries_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(503)
+            .body(json!({"message": "Service Unavailable"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--retry-max-elapsed", "2s"])
+        .unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Request failed - HTTP 503: Service Unavailable, retrying in"));
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Polling failed: Giving up after"));
+    assert!(stderr.contains("HTTP 503: Service Unavailable"));
+    assert!(deliveries_mock.calls() > 1);
+    delete_mock.assert();
+}
+
+#[test]
+fn test_idle_timeout_shuts_down() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    let delete_mock = gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body("[]");
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--interval", "2s", "--idle-timeout", "1s"])
+        .unwrap().wait_with_output().unwrap();
+
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Polling timed out after 1s of inactivity"));
+    delete_mock.assert();
+}
+
+#[test]
+fn test_keep_alive_survives_idle_timeout() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body("[]");
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--interval", "2s", "--idle-timeout", "1s", "--keep-alive"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    assert!(child.try_wait().unwrap().is_none(), "process exited at the idle timeout");
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No deliveries for a while, polling every 5 minutes until one shows up"));
+}
+
+#[test]
+fn test_polling_backs_off_while_idle() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body("[]");
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli(vec!["--debug", "forward", "--github-host", host.as_str(), "--repo", "org/repo", "--interval", "2s", "--max-interval", "3s"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Next poll in 2.0s"));
+    assert!(stdout.contains("Next poll in 3.0s"));
+}
+
+#[test]
+fn test_missed_deliveries_are_reported_at_shutdown() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([{"id": 5, "delivered_at": (chrono::Utc::now() + chrono::Duration::seconds(1)).to_rfc3339(), "event": "issues"}]).to_string());
+    });
+    let details_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/5");
+        then.status(500)
+            .body(json!({"message": "Server Error"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--interval", "2s", "--idle-timeout", "1s"])
+        .unwrap().wait_with_output().unwrap();
+
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Error getting delivery details for 5, trying again in 5s"));
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Permanently missed 1 delivery(s): 5"));
+    details_mock.assert_calls(1);
+}
+
+#[test]
+fn test_interval_below_minimum_is_rejected() {
+    let result = run_cli_forward(vec!["--repo", "org/repo", "--interval", "500ms"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("must be at least 2s"));
+}
+
+#[test]
+fn test_resume_forwards_deliveries_since_cursor() {
+    let gh_server = MockGhServer::new();
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks");
+        then.status(500);
+    });
+    gh_server.add_get_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([delivery(3), delivery(2), delivery(1)]).to_string());
+    });
+    gh_server.add_get_webhook_delivery_details_with_id(2);
+    gh_server.add_get_webhook_delivery_details_with_id(3);
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let config_dir = temp_config_dir("resume");
+    let cursor = json!({"webhook_id": 1, "last_delivery_id": 1, "timestamp": "2024-08-01T00:00:00Z"});
+    let cursor_path = cursor_path(&config_dir, &format!("http://{}/repos/org/repo/hooks", host));
+    std::fs::create_dir_all(cursor_path.parent().unwrap()).unwrap();
+    std::fs::write(cursor_path, cursor.to_string()).unwrap();
+
+    let mut child = run_cli_forward_with_config_dir(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--resume"], &config_dir);
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Reattached to webhook 1"));
+    assert!(!stdout.contains("Forwarding event: 1"));
+    assert!(stdout.contains("Forwarding event: 2"));
+    assert!(stdout.contains("Forwarding event: 3"));
+    assert_eq!(read_cursor(&config_dir, &format!("http://{}/repos/org/repo/hooks", host))["last_delivery_id"], 3);
+    create_mock.assert_calls(0);
+    std::fs::remove_dir_all(config_dir).unwrap();
+}
 
-    assert!(!result.status.success());
-    assert!(String::from_utf8_lossy(&result.stderr).contains("Organization webhooks are not supported."));
+#[test]
+fn test_resume_cursor_does_not_move_back_for_retried_delivery() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([delivery(7), delivery(6), delivery(5)]).to_string());
+    });
+    let mut failing = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/5");
+        then.status(500);
+    });
+    gh_server.add_get_webhook_delivery_details_with_id(6);
+    gh_server.add_get_webhook_delivery_details_with_id(7);
+    let host = format!("localhost:{}", gh_server.server.port());
+    let config_dir = temp_config_dir("resume-retry");
+
+    let mut child = run_cli_forward_with_config_dir(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--resume", "--interval", "2s"], &config_dir);
+
+    // 6 and 7 go out first, then 5 comes through on the retry
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    failing.assert_calls(1);
+    failing.delete();
+    gh_server.add_get_webhook_delivery_details_with_id(5);
+    // the retry is due 5 seconds after the failure, and picked up by the first poll after that
+    std::thread::sleep(std::time::Duration::from_secs(8));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let retried = stdout.find("Forwarding event: 5").expect("delivery 5 was not retried");
+    assert!(stdout.find("Forwarding event: 7").unwrap() < retried);
+    assert_eq!(read_cursor(&config_dir, &format!("http://{}/repos/org/repo/hooks", host))["last_delivery_id"], 7);
+    std::fs::remove_dir_all(config_dir).unwrap();
 }
 
-#[cfg(not(target_os = "windows"))]
 #[test]
-fn test_ctrl_c() {
+fn test_resume_cursor_stays_put_when_forwarding_fails() {
     let gh_server = MockGhServer::new();
     gh_server.add_all_mocks();
     let host = format!("localhost:{}", gh_server.server.port());
+    let config_dir = temp_config_dir("resume-forward-fails");
 
-    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+    // nothing listens on port 1
+    let mut child = run_cli_forward_with_config_dir(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--resume", "--url", "localhost:1"], &config_dir);
 
-    // sleep for a second to allow the CLI to start
     std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
 
-    // send a SIGINT to the child process (doesn't work that way on windows)
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Error forwarding payload"));
+    assert_eq!(read_cursor(&config_dir, &format!("http://{}/repos/org/repo/hooks", host))["last_delivery_id"], serde_json::Value::Null);
+    std::fs::remove_dir_all(config_dir).unwrap();
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_resume_cursor_stays_below_failed_forward() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_delivery_details_with_id(1);
+    gh_server.add_get_webhook_delivery_details_with_id(2);
+    let mut first_batch = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([delivery(1)]).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+    let config_dir = temp_config_dir("resume-failed-forward");
+
+    // the local server isn't up yet when 1 is forwarded, only by the time 2 is
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let url = format!("127.0.0.1:{}", port);
+    let child = run_cli_forward_with_config_dir(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--resume", "--interval", "2s", "--url", url.as_str()], &config_dir);
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    first_batch.delete();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([delivery(2), delivery(1)]).to_string());
+    });
+    accept_forwards(std::net::TcpListener::bind(("127.0.0.1", port)).unwrap());
+    std::thread::sleep(std::time::Duration::from_secs(2));
     nix::sys::signal::kill(
         nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::SIGINT
     ).unwrap();
 
-    let result = child.wait_with_output().unwrap();
-    assert!(result.status.success());
-    assert!(String::from_utf8_lossy(&result.stdout).contains("Deleting CLI webhook"));
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 2"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Permanently missed 1 delivery(s): 1"));
+    assert_eq!(read_cursor(&config_dir, &format!("http://{}/repos/org/repo/hooks", host))["last_delivery_id"], serde_json::Value::Null);
+    std::fs::remove_dir_all(config_dir).unwrap();
 }
 
 #[test]
-fn test_forward_to_stdout() {
+fn test_resume_keeps_webhook() {
     let gh_server = MockGhServer::new();
-    gh_server.add_all_mocks();
+    gh_server.add_create_webhook();
+    let delete_mock = gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body("[]");
+    });
     let host = format!("localhost:{}", gh_server.server.port());
+    let config_dir = temp_config_dir("resume-keep");
 
-    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--events", "issues,pull_request"]).unwrap();
+    let output = run_cli_forward_with_config_dir(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--resume", "--interval", "2s", "--idle-timeout", "1s"], &config_dir)
+        .wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Webhook 1 is kept when this session ends"));
+    let cursor = &read_cursor(&config_dir, &format!("http://{}/repos/org/repo/hooks", host));
+    assert_eq!(cursor["webhook_id"], 1);
+    assert!(cursor["last_delivery_id"].is_null());
+    delete_mock.assert_calls(0);
+    std::fs::remove_dir_all(config_dir).unwrap();
+}
+
+#[test]
+fn test_rate_limited_polling_keeps_running() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    let reset = (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp().to_string();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(403)
+            .header("x-ratelimit-limit", "5000")
+            .header("x-ratelimit-remaining", "0")
+            .header("x-ratelimit-reset", reset.as_str())
+            .body(json!({"message": "API rate limit exceeded"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    assert!(child.try_wait().unwrap().is_none(), "process exited after being rate limited");
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Rate limited by GitHub, pausing polling until"));
+}
+
+#[test]
+fn test_rate_limit_pause_is_not_idle_time() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    // anything resetting within a minute is waited out by the client, past that the poller pauses
+    let reset = (chrono::Utc::now() + chrono::Duration::seconds(65)).timestamp().to_string();
+    let mut rate_limited = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(403)
+            .header("x-ratelimit-limit", "5000")
+            .header("x-ratelimit-remaining", "0")
+            .header("x-ratelimit-reset", reset.as_str())
+            .body(json!({"message": "API rate limit exceeded"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--idle-timeout", "1s"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    rate_limited.assert_calls(1);
+    rate_limited.delete();
+    let deliveries = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body("[]");
+    });
+
+    // the pause outlasts the idle timeout, polling still has to pick up again afterwards
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Polling timed out"));
+    deliveries.assert_calls(1);
+}
+
+#[test]
+fn test_conditional_polling() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_delivery_details();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .header_missing("if-none-match");
+        then.status(200)
+            .header("ETag", "\"abc\"")
+            .body(json!([delivery(1)]).to_string());
+    });
+    let not_modified = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .header("if-none-match", "\"abc\"");
+        then.status(304);
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli(vec!["--debug", "forward", "--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    // long enough for a second poll
+    std::thread::sleep(std::time::Duration::from_secs(6));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Forwarding event: 1"));
+    assert!(stdout.contains("has not changed (1 conditional hits so far)"));
+    assert_eq!(stdout.matches("Forwarding event: 1").count(), 1);
+    not_modified.assert();
+}
+
+#[test]
+fn test_forward_paginated_deliveries() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_delivery_details();
+    gh_server.add_get_webhook_delivery_details_with_id(2);
+    let next = format!("<{}>; rel=\"next\"", gh_server.server.url("/repos/org/repo/hooks/1/deliveries?per_page=100&cursor=page2"));
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .query_param_missing("cursor");
+        then.status(200)
+            .header("Link", next.as_str())
+            .body(json!([delivery(2)]).to_string());
+    });
+    let second_page = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .query_param("cursor", "page2");
+        then.status(200)
+            .body(json!([delivery(1)]).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let first = stdout.find("Forwarding event: 1").expect("delivery from the second page was not forwarded");
+    let second = stdout.find("Forwarding event: 2").expect("delivery from the first page was not forwarded");
+    assert!(first < second, "deliveries were not forwarded oldest first");
+    second_page.assert();
+}
+
+#[test]
+fn test_conditional_polling_walks_again_after_failed_page() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_delivery_details();
+    gh_server.add_get_webhook_delivery_details_with_id(2);
+    let next = format!("<{}>; rel=\"next\"", gh_server.server.url("/repos/org/repo/hooks/1/deliveries?per_page=100&cursor=page2"));
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .query_param_missing("cursor")
+            .header_missing("if-none-match");
+        then.status(200)
+            .header("ETag", "\"abc\"")
+            .header("Link", next.as_str())
+            .body(json!([delivery(2)]).to_string());
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .query_param_missing("cursor")
+            .header("if-none-match", "\"abc\"");
+        then.status(304);
+    });
+    // long enough that the poller pauses instead of the client waiting it out
+    let reset = (chrono::Utc::now() + chrono::Duration::seconds(65)).timestamp().to_string();
+    let mut rate_limited = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .query_param("cursor", "page2");
+        then.status(403)
+            .header("x-ratelimit-limit", "5000")
+            .header("x-ratelimit-remaining", "0")
+            .header("x-ratelimit-reset", reset.as_str())
+            .body(json!({"message": "API rate limit exceeded"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    rate_limited.assert_calls(1);
+    rate_limited.delete();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries")
+            .query_param("cursor", "page2");
+        then.status(200)
+            .body(json!([delivery(1)]).to_string());
+    });
+    std::thread::sleep(std::time::Duration::from_secs(68));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Forwarding event: 1"));
+    assert!(stdout.contains("Forwarding event: 2"));
+}
+
+#[test]
+fn test_concurrent_details_are_forwarded_in_order() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    gh_server.add_delete_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([delivery(4), delivery(3), delivery(2), delivery(1)]).to_string());
+    });
+    // the oldest delivery is the slowest to fetch, the rest have to wait for it
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/1");
+        then.status(200)
+            .delay(std::time::Duration::from_millis(500))
+            .body(delivery_details(1).to_string());
+    });
+    for id in 2..=4 {
+        gh_server.add_get_webhook_delivery_details_with_id(id);
+    }
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--fetch-concurrency", "4"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    child.kill().unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let positions: Vec<usize> = (1..=4)
+        .map(|id| stdout.find(&format!("Forwarding event: {}", id)).unwrap_or_else(|| panic!("delivery {} was not forwarded", id)))
+        .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "deliveries were not forwarded in order");
+}
+
+#[test]
+fn test_token_file_without_gh() {
+    let gh_server = MockGhServer::new();
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks")
+            .header("authorization", "Bearer gh_filetoken");
+        then.status(201)
+            .body(json!({"id": 1, "name": "cli", "events": ["push"], "active": true}).to_string());
+    });
+    gh_server.add_delete_webhook();
+    gh_server.add_get_webhook_deliveries();
+    gh_server.add_get_webhook_delivery_details();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_without_gh(vec!["forward", "--github-host", host.as_str(), "--repo", "org/repo", "--token-file", "tests/fixtures/token"]);
 
-    // sleep for a second to allow the CLI to grab webhook deliveries
     std::thread::sleep(std::time::Duration::from_secs(1));
     child.kill().unwrap();
 
     let output = child.wait_with_output().unwrap();
+    create_mock.assert();
     assert!(String::from_utf8_lossy(&output.stdout).contains("Forwarding event: 1"));
-    assert!(String::from_utf8_lossy(&output.stdout).contains("Content-Type: application/json"));
-    assert!(String::from_utf8_lossy(&output.stdout).contains("{\"issue\":{\"body\":\"Test Body\",\"title\":\"Test Issue\"}}"));
 }
 
 #[test]
-fn test_foward_to_local_server() {
-    // mock gh server
+fn test_no_token_without_gh() {
     let gh_server = MockGhServer::new();
     gh_server.add_all_mocks();
     let host = format!("localhost:{}", gh_server.server.port());
 
-    // mock local server
-    let mock_reciever = MockServer::start();
-    let mock_reciever_endpoint= create_mock_reciever_endpoint(&mock_reciever);
-    let url = format!("localhost:{}/test", mock_reciever.port());
+    let result = run_cli_without_gh(vec!["forward", "--github-host", host.as_str(), "--repo", "org/repo"]).wait_with_output().unwrap();
 
-    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo", "--url", url.as_str()]).unwrap();
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("No GitHub token found for localhost"));
+    assert!(stderr.contains("`gh` was not found on the PATH"));
+}
 
-    // sleep for a second to allow the CLI to grab webhook deliveries. should forward once
-    std::thread::sleep(std::time::Duration::from_secs(7));
+#[test]
+fn test_cleanup() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_list_webhooks();
+    let delete_mock = gh_server.add_delete_webhook();
+    let delete_other_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::DELETE)
+            .path("/repos/org/repo/hooks/2");
+        then.status(204);
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli(vec!["cleanup", "--github-host", host.as_str(), "--repo", "org/repo", "--yes"]).unwrap().wait_with_output().unwrap();
+
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Deleted webhook 1"));
+    delete_mock.assert();
+    delete_other_mock.assert_calls(0);
+}
+
+#[test]
+fn test_cleanup_unmarked_webhooks_only_when_asked() {
+    let gh_server = MockGhServer::new();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks");
+        then.status(200)
+            .body(json!([{
+                "id": 1,
+                "name": "cli",
+                "events": ["issues"],
+                "active": true,
+                "config": {"content_type": "json"},
+                "created_at": "2024-08-01T00:00:00Z"
+            }]).to_string());
+    });
+    let delete_mock = gh_server.add_delete_webhook();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    // could just as well be a live `gh webhook forward` session
+    let result = run_cli(vec!["cleanup", "--github-host", host.as_str(), "--repo", "org/repo", "--yes"]).unwrap().wait_with_output().unwrap();
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("No webhooks created by gh-ghes-webhook found"));
+    delete_mock.assert_calls(0);
+
+    let result = run_cli(vec!["cleanup", "--github-host", host.as_str(), "--repo", "org/repo", "--yes", "--include-unmarked"]).unwrap().wait_with_output().unwrap();
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Deleted webhook 1"));
+    delete_mock.assert();
+}
+
+#[test]
+fn test_cleanup_requires_confirmation() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_list_webhooks();
+    let delete_mock = gh_server.add_delete_webhook();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli(vec!["cleanup", "--github-host", host.as_str(), "--repo", "org/repo"]).unwrap().wait_with_output().unwrap();
+
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Nothing was deleted"));
+    delete_mock.assert_calls(0);
+}
+
+#[test]
+fn test_forward_reports_stale_webhooks() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_all_mocks();
+    gh_server.add_list_webhooks();
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let mut child = run_cli_forward(vec!["--github-host", host.as_str(), "--repo", "org/repo"]).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
     child.kill().unwrap();
 
-    mock_reciever_endpoint.assert();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Found 1 webhook(s) left behind by previous sessions"));
+    assert!(stdout.contains("Forwarding event: 1"));
+}
+
+#[test]
+fn test_ping() {
+    let gh_server = MockGhServer::new();
+    let create_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks")
+            .json_body_includes(json!({"events": ["push"]}).to_string());
+        then.status(200)
+            .body(json!({"id": 1, "config": {}, "name": "cli", "events": ["push"], "active": true}).to_string());
+    });
+    let delete_mock = gh_server.add_delete_webhook();
+    let ping_mock = gh_server.add_ping_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body(json!([{
+                "id": 7,
+                "delivered_at": "2099-08-01T00:00:00Z",
+                "event": "ping",
+                "action": null
+            }]).to_string());
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries/7");
+        then.status(200)
+            .body(json!({
+                "id": 7,
+                "delivered_at": "2099-08-01T00:00:00Z",
+                "event": "ping",
+                "action": null,
+                "request": {
+                    "headers": {"X-GitHub-Event": "ping"},
+                    "payload": {"zen": "Keep it logically awesome.", "hook_id": 1}
+                }
+            }).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli(vec!["ping", "--github-host", host.as_str(), "--repo", "org/repo"]).unwrap().wait_with_output().unwrap();
+
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(result.status.success(), "{}", stdout);
+    assert!(stdout.contains("Webhook created"));
+    assert!(stdout.contains("Ping sent"));
+    assert!(stdout.contains("Delivery details fetched"));
+    assert!(stdout.contains("Delivery forwarded"));
+    create_mock.assert();
+    ping_mock.assert();
+    delete_mock.assert();
+}
+
+#[test]
+fn test_ping_times_out() {
+    let gh_server = MockGhServer::new();
+    gh_server.add_create_webhook();
+    let delete_mock = gh_server.add_delete_webhook();
+    gh_server.add_ping_webhook();
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::GET)
+            .path("/repos/org/repo/hooks/1/deliveries");
+        then.status(200)
+            .body("[]");
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli(vec!["ping", "--github-host", host.as_str(), "--repo", "org/repo", "--timeout", "2"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("No ping delivery showed up within 2 seconds"));
+    delete_mock.assert();
+}
+
+#[test]
+fn test_redeliver() {
+    let gh_server = MockGhServer::new();
+    let attempts_mock = gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks/1/deliveries/12/attempts");
+        then.status(202)
+            .body("{}");
+    });
+    gh_server.server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/repos/org/repo/hooks/1/deliveries/13/attempts");
+        then.status(404)
+            .body(json!({"message": "Not Found"}).to_string());
+    });
+    let host = format!("localhost:{}", gh_server.server.port());
+
+    let result = run_cli(vec!["redeliver", "--github-host", host.as_str(), "--repo", "org/repo", "--webhook-id", "1", "12", "13"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("Requested redelivery of 12"));
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Failed to redeliver 13: Not found"));
+    attempts_mock.assert();
+}
+
+#[test]
+fn test_redeliver_requires_webhook_id() {
+    let result = run_cli(vec!["redeliver", "--repo", "org/repo", "12"]).unwrap().wait_with_output().unwrap();
+
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--webhook-id"));
 }
 
 
 fn run_cli_forward(mut args: Vec<&str>) -> Result<Child, ()> {
     args.insert(0, "forward");
+    run_cli(args)
+}
+
+/// Run `forward` with its own gh config dir, where --resume keeps its state
+fn run_cli_forward_with_config_dir(mut args: Vec<&str>, config_dir: &Path) -> Child {
+    args.insert(0, "forward");
+    Command::new("target/debug/gh-ghes-webhook")
+        .env("PATH", add_mock_gh_to_path())
+        .env("GH_CONFIG_DIR", config_dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
 
+fn temp_config_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("gh-ghes-webhook-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Where the cursor for a location is saved, named the way the CLI names it
+fn cursor_path(config_dir: &Path, key: &str) -> PathBuf {
+    let file_name: String = key.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' => (b as char).to_string(),
+            b => format!("_{:02X}", b),
+        })
+        .collect();
+    config_dir.join("gh-ghes-webhook").join("cursors").join(format!("{}.json", file_name))
+}
+
+fn read_cursor(config_dir: &Path, key: &str) -> serde_json::Value {
+    let json = std::fs::read(cursor_path(config_dir, key)).unwrap();
+    serde_json::from_slice(&json).unwrap()
+}
+
+fn run_cli(args: Vec<&str>) -> Result<Child, ()> {
     let child = Command::new("target/debug/gh-ghes-webhook")
         .env("PATH", add_mock_gh_to_path())
         .args(args)
+        .stdin(Stdio::null())
         .stderr(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn();
@@ -111,6 +1524,21 @@ fn run_cli_forward(mut args: Vec<&str>) -> Result<Child, ()> {
     }
 }
 
+/// Run the CLI with a PATH that has no `gh` on it and no tokens in the environment
+fn run_cli_without_gh(args: Vec<&str>) -> Child {
+    let empty_path = env::current_dir().unwrap().join("tests").join("fixtures");
+    Command::new("target/debug/gh-ghes-webhook")
+        .env("PATH", empty_path)
+        .env_remove("GH_ENTERPRISE_TOKEN")
+        .env_remove("GITHUB_ENTERPRISE_TOKEN")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
 fn add_mock_gh_to_path() -> String {
     let current_dir = env::current_dir().unwrap();
     let test_path = current_dir.join("tests").join("bin");
@@ -120,6 +1548,30 @@ fn add_mock_gh_to_path() -> String {
     env::join_paths(paths).unwrap().to_string_lossy().to_string()
 }
 
+fn delivery(id: u64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "delivered_at": "2099-08-01T00:00:00Z",
+        "event": "issues",
+        "action": "opened"
+    })
+}
+
+fn delivery_details(id: u64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "delivered_at": "2021-08-01T00:00:00Z",
+        "event": "issues",
+        "action": "opened",
+        "request": {
+            "headers": {
+                "Content-Type": "application/json"
+            },
+            "payload": payload()
+        }
+    })
+}
+
 fn payload() -> serde_json::Value {
     json!({
         "issue": {
@@ -130,7 +1582,32 @@ fn payload() -> serde_json::Value {
 }
 
 
-fn create_mock_reciever_endpoint(server: &MockServer) -> httpmock::Mock {
+/// Answer every request on the listener with an empty 200, for a local server that has to come up
+/// on a port picked ahead of time
+fn accept_forwards(listener: std::net::TcpListener) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut content_length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+        }
+    });
+}
+
+fn create_mock_reciever_endpoint(server: &MockServer) -> httpmock::Mock<'_> {
     server.mock(|when, then| {
         when.method(httpmock::Method::POST)
             .path("/test")
@@ -142,12 +1619,18 @@ fn create_mock_reciever_endpoint(server: &MockServer) -> httpmock::Mock {
 
 struct MockGhServer {
     server: MockServer,
+    hooks: String,
 }
 
 impl MockGhServer {
     fn new() -> Self {
+        MockGhServer::with_hooks_path("/repos/org/repo/hooks")
+    }
+
+    fn with_hooks_path(hooks: &str) -> Self {
         MockGhServer {
-            server: MockServer::start()
+            server: MockServer::start(),
+            hooks: hooks.to_string(),
         }
     }
 
@@ -161,7 +1644,7 @@ impl MockGhServer {
     fn add_create_webhook(&self) {
         self.server.mock(|when, then| {
             when.method(httpmock::Method::POST)
-                .path("/repos/org/repo/hooks");
+                .path(self.hooks.as_str());
             then.status(200)
                 .body(json!({
                     "id": 1,
@@ -177,47 +1660,85 @@ impl MockGhServer {
         });
     }
 
-    fn add_delete_webhook(&self) {
-        self.server.mock(|when, then| {
-            when.method(httpmock::Method::DELETE)
-                .path("/repos/org/repo/hooks/1");
-            then.status(204);
-        });
-    }
-
-    fn add_get_webhook_deliveries(&self) {
+    fn add_list_webhooks(&self) {
         self.server.mock(|when, then| {
             when.method(httpmock::Method::GET)
-                .path("/repos/org/repo/hooks/1/deliveries");
+                .path(self.hooks.as_str());
             then.status(200)
                 .body(json!([
                     {
                         "id": 1,
-                        "delivered_at": "2099-08-01T00:00:00Z",
-                        "event": "issues",
-                        "action": "opened"
+                        "name": "cli",
+                        "events": ["issues"],
+                        "active": true,
+                        "config": {"content_type": "json", "gh_ghes_webhook": "1.1.0"},
+                        "created_at": "2024-08-01T00:00:00Z"
+                    },
+                    {
+                        "id": 2,
+                        "name": "web",
+                        "events": ["push"],
+                        "active": true,
+                        "config": {"url": "https://example.com/webhook", "content_type": "json"},
+                        "created_at": "2024-08-01T00:00:00Z"
                     }
                 ]).to_string());
         });
     }
 
-    fn add_get_webhook_delivery_details(&self) {
+    fn add_ping_webhook(&self) -> httpmock::Mock<'_> {
+        self.server.mock(|when, then| {
+            when.method(httpmock::Method::POST)
+                .path(format!("{}/1/pings", self.hooks));
+            then.status(204);
+        })
+    }
+
+    fn add_get_webhook(&self) {
         self.server.mock(|when, then| {
             when.method(httpmock::Method::GET)
-                .path("/repos/org/repo/hooks/1/deliveries/1");
+                .path(format!("{}/1", self.hooks));
             then.status(200)
                 .body(json!({
                     "id": 1,
-                    "delivered_at": "2021-08-01T00:00:00Z",
-                    "event": "issues",
-                    "action": "opened",
-                    "request": {
-                        "headers": {
-                            "Content-Type": "application/json"
-                        },
-                        "payload": payload()
+                    "name": "web",
+                    "events": ["issues", "pull_request"],
+                    "active": true,
+                    "config": {
+                        "url": "https://example.com/webhook",
+                        "content_type": "json"
                     }
                 }).to_string());
         });
     }
+
+    fn add_delete_webhook(&self) -> httpmock::Mock<'_> {
+        self.server.mock(|when, then| {
+            when.method(httpmock::Method::DELETE)
+                .path(format!("{}/1", self.hooks));
+            then.status(204);
+        })
+    }
+
+    fn add_get_webhook_deliveries(&self) {
+        self.server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path(format!("{}/1/deliveries", self.hooks));
+            then.status(200)
+                .body(json!([delivery(1)]).to_string());
+        });
+    }
+
+    fn add_get_webhook_delivery_details(&self) {
+        self.add_get_webhook_delivery_details_with_id(1);
+    }
+
+    fn add_get_webhook_delivery_details_with_id(&self, id: u64) {
+        self.server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path(format!("{}/1/deliveries/{}", self.hooks, id));
+            then.status(200)
+                .body(delivery_details(id).to_string());
+        });
+    }
 }